@@ -1,70 +1,271 @@
-use tcod::colors::*;
-
-use crate::*;
-
-#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub enum Ai {
-    Basic,
-    Confused {
-        previous_ai: Box<Ai>,
-        num_turns: i32,
-    }
-}
-
-pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects: &mut [Object]) {
-    // Vector from this object to the target (and distance)
-    let dx = target_x - objects[id].x;
-    let dy = target_y - objects[id].y;
-    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
-
-    // Normalise the vector and round to nearest int
-    let dx = (dx as f32 / distance).round() as i32;
-    let dy = (dy as f32 / distance).round() as i32;
-    move_by(id, dx, dy, map, objects);
-}
-
-pub fn ai_take_turn(monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) {
-    use Ai::*;
-    if let Some(ai) = objects[monster_id].ai.take() {
-        let new_ai = match ai {
-            Basic => ai_basic(monster_id, tcod, game, objects),
-            Confused {
-                previous_ai,
-                num_turns,
-            } => ai_confused(monster_id, tcod, game, objects, previous_ai, num_turns),
-        };
-        objects[monster_id].ai = Some(new_ai);
-    }
-}
-
-pub fn ai_basic(monster_id: usize, tcod: &Tcod, game: &mut Game, objects: &mut [Object]) -> Ai {
-    // Basic monster takes its turn. If you can see it, it can see you
-    let (monster_x, monster_y) = objects[monster_id].pos();
-    if tcod.fov.is_in_fov(monster_x, monster_y) {
-        if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 {
-            // Move towards player if far away
-            let (player_x, player_y) = objects[PLAYER].pos();
-            move_towards(monster_id, player_x, player_y, &game.map, objects);
-        } else if objects[PLAYER].fighter.map_or(false, |f| f.hp > 0) {
-            // Close enough, attack! (if the player is still alive.)
-            let (monster, player) = mut_two(monster_id, PLAYER, objects);
-            monster.attack(player, game);
-        }
-    }
-    Ai::Basic
-}
-
-fn ai_confused(monster_id: usize, _tcod: &Tcod, game: &mut Game, objects: &mut [Object], previous_ai: Box<Ai>, num_turns: i32) -> Ai {
-    if num_turns >= 0 {
-        // Move in random direction, and decrease the number of turns confused
-        move_by(monster_id, rand::thread_rng().gen_range(-1, 2), rand::thread_rng().gen_range(-1, 2), &game.map, objects);
-        Ai::Confused {
-            previous_ai: previous_ai,
-            num_turns: num_turns - 1,
-        }
-    } else {
-        // Restore the previous AI (this one will be deleted)
-        game.messages.add( format!("The {} is no longer confused!", objects[monster_id].name), RED.into());
-        *previous_ai
-    }
-}
\ No newline at end of file
+use crate::*;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Ai {
+    /// Sleeps until woken by a noise close by
+    Asleep,
+    /// Awake, but hasn't noticed the player yet
+    Basic,
+    /// Lost sight of the player, and heads for where they were last seen
+    Tracking {
+        last_seen: (i32, i32),
+        turns_left: i32,
+    },
+    /// Gave up the chase, and roams about until the player shows up again
+    Wandering {
+        destination: (i32, i32),
+    },
+}
+
+impl Ai {
+    /// Whether a monster with this AI is unaware of the player, and can be ambushed
+    pub fn is_unaware(&self) -> bool {
+        matches!(self, Ai::Asleep | Ai::Basic | Ai::Wandering { .. })
+    }
+}
+
+/// How well a monster picks up on the player
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Senses {
+    pub sight_radius: i32,
+    pub perception: i32,    // Chance out of 100 each turn to notice the player in plain sight
+    pub hearing: i32,       // Percentage of the player's noise radius it hears
+}
+
+/// Step straight towards the target if possible, otherwise take the first step of a path around
+/// whatever is in the way. Behind a crowd with no way round, this waits for it to move
+pub fn chase(id: ObjectId, target_x: i32, target_y: i32, map: &Map, objects: &mut Objects) {
+    spend_energy(id, MOVE_COST, objects);
+    let (x, y) = objects.pos(id);
+    let (dx, dy) = direction_towards((x, y), target_x, target_y);
+    if !is_blocked(x + dx, y + dy, map, objects) {
+        move_by(id, dx, dy, map, objects);
+    } else if let Some(&(next_x, next_y)) = find_path((x, y), (target_x, target_y), map, objects).as_deref().and_then(<[_]>::first) {
+        move_by(id, next_x - x, next_y - y, map, objects);
+    }
+}
+
+fn direction_towards((x, y): (i32, i32), target_x: i32, target_y: i32) -> (i32, i32) {
+    // Vector from this object to the target (and distance)
+    let dx = target_x - x;
+    let dy = target_y - y;
+    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
+
+    // Normalise the vector and round to nearest int
+    let dx = (dx as f32 / distance).round() as i32;
+    let dy = (dy as f32 / distance).round() as i32;
+    (dx, dy)
+}
+
+pub fn ai_take_turn(monster_id: ObjectId, game: &mut Game, objects: &mut Objects) {
+    use Ai::*;
+    let energy = objects.fighters.get(monster_id).map(|f| f.energy);
+    let start = objects.pos(monster_id);
+
+    if objects.ais.contains(monster_id) && objects.has_status(monster_id, StatusKind::Confused) {
+        // Confusion takes over for as long as it lasts, whatever the monster was up to
+        ai_confused(monster_id, game, objects);
+    } else if is_badly_hurt(monster_id, objects) && drink_healing_potion(monster_id, game, objects) {
+        spend_energy(monster_id, USE_ITEM_COST, objects);
+    } else if let Some(ai) = objects.ais.remove(monster_id) {
+        let new_ai = match ai {
+            Asleep => ai_asleep(monster_id, game, objects),
+            Basic => ai_basic(monster_id, game, objects),
+            Tracking {
+                last_seen,
+                turns_left,
+            } => ai_tracking(monster_id, game, objects, last_seen, turns_left),
+            Wandering { destination } => ai_wandering(monster_id, game, objects, destination),
+        };
+        objects.ais.insert(monster_id, new_ai);
+    }
+
+    // A monster that did nothing else spent its time waiting
+    if objects.fighters.get(monster_id).map(|f| f.energy) == energy {
+        spend_energy(monster_id, WAIT_COST, objects);
+    }
+
+    // Anything lying where it ends up is picked up on the way
+    let (x, y) = objects.pos(monster_id);
+    if (x, y) != start && objects.inventories.contains(monster_id) {
+        let item_id = objects.at(x, y).find(|&id| objects.items.contains(id));
+        if let Some(item_id) = item_id {
+            pick_item_up(monster_id, item_id, game, objects);
+        }
+    }
+}
+
+/// Whether an awake monster is hurt enough to drink a healing potion, if it has one
+fn is_badly_hurt(monster_id: ObjectId, objects: &Objects) -> bool {
+    let awake = objects.ais.get(monster_id).is_some_and(|ai| *ai != Ai::Asleep);
+    let hp = objects.fighters.get(monster_id).map_or(0, |f| f.hp);
+    awake && hp * 100 <= objects.max_hp(monster_id) * MONSTER_HEAL_PERCENT
+}
+
+fn ai_asleep(monster_id: ObjectId, game: &mut Game, objects: &mut Objects) -> Ai {
+    // Sleeping monsters only hear half as far, and see nothing at all
+    if objects.distance_to(monster_id, PLAYER) > hearing_range(monster_id, game, objects) / 2.0 {
+        return Ai::Asleep;
+    }
+
+    let (x, y) = objects.pos(monster_id);
+    if game.fov.is_in_fov(x, y) {
+        game.messages.add(format!("The {} wakes up!", objects.names[monster_id]), ORANGE);
+    }
+    Ai::Tracking {
+        last_seen: objects.pos(PLAYER),
+        turns_left: MONSTER_MEMORY_TURNS,
+    }
+}
+
+pub fn ai_basic(monster_id: ObjectId, game: &mut Game, objects: &mut Objects) -> Ai {
+    // Basic monster idles until it notices the player
+    if notices_player(monster_id, game, objects) {
+        hunt(monster_id, game, objects)
+    } else {
+        Ai::Basic
+    }
+}
+
+fn ai_tracking(monster_id: ObjectId, game: &mut Game, objects: &mut Objects, last_seen: (i32, i32), turns_left: i32) -> Ai {
+    if sees_player(monster_id, game, objects) {
+        return hunt(monster_id, game, objects);
+    }
+
+    // Any noise gives the player away again
+    let (last_seen, turns_left) = if objects.distance_to(monster_id, PLAYER) <= hearing_range(monster_id, game, objects) {
+        (objects.pos(PLAYER), MONSTER_MEMORY_TURNS)
+    } else {
+        (last_seen, turns_left)
+    };
+
+    if turns_left <= 0 || objects.pos(monster_id) == last_seen {
+        // The trail has gone cold
+        let destination = wander_destination(objects.pos(monster_id), &game.map, &mut game.rng);
+        return Ai::Wandering { destination };
+    }
+
+    chase(monster_id, last_seen.0, last_seen.1, &game.map, objects);
+    Ai::Tracking {
+        last_seen,
+        turns_left: turns_left - 1,
+    }
+}
+
+fn ai_wandering(monster_id: ObjectId, game: &mut Game, objects: &mut Objects, destination: (i32, i32)) -> Ai {
+    if notices_player(monster_id, game, objects) {
+        return hunt(monster_id, game, objects);
+    }
+
+    let previous_position = objects.pos(monster_id);
+    chase(monster_id, destination.0, destination.1, &game.map, objects);
+
+    // Pick somewhere else once there, or when something is in the way
+    let position = objects.pos(monster_id);
+    let destination = if position == destination || position == previous_position {
+        wander_destination(position, &game.map, &mut game.rng)
+    } else {
+        destination
+    };
+    Ai::Wandering { destination }
+}
+
+/// Whether the player is within the monster's sight radius, with nothing in the way
+fn sees_player(monster_id: ObjectId, game: &Game, objects: &Objects) -> bool {
+    let sight_radius = objects.senses.get(monster_id).map_or(0, |s| s.sight_radius);
+    objects.distance_to(monster_id, PLAYER) <= sight_radius as f32
+        && has_line_of_sight(objects.pos(monster_id), objects.pos(PLAYER), &game.map)
+}
+
+/// How far away the monster hears what the player did last. Walls don't stop sound
+fn hearing_range(monster_id: ObjectId, game: &Game, objects: &Objects) -> f32 {
+    let hearing = objects.senses.get(monster_id).map_or(0, |s| s.hearing);
+    (game.noise * hearing) as f32 / 100.0
+}
+
+/// Whether a monster that isn't looking for the player picks up on them this turn.
+/// Noise always gives the player away, being in sight only does if the monster is perceptive enough
+fn notices_player(monster_id: ObjectId, game: &mut Game, objects: &Objects) -> bool {
+    if objects.distance_to(monster_id, PLAYER) <= hearing_range(monster_id, game, objects) {
+        return true;
+    }
+
+    let perception = objects.senses.get(monster_id).map_or(0, |s| s.perception);
+    sees_player(monster_id, game, objects) && game.rng.gen_range(0, 100) < perception
+}
+
+/// Close in on the player and attack, remembering where they were for when they get out of sight.
+/// Monsters with a ranged attack keep their distance and shoot instead, whenever they have a clear shot
+fn hunt(monster_id: ObjectId, game: &mut Game, objects: &mut Objects) -> Ai {
+    let player_pos = objects.pos(PLAYER);
+    let player_alive = objects.is_alive(PLAYER);
+    let distance = objects.distance_to(monster_id, PLAYER);
+    let ranged_attack = match objects.ranged.get(monster_id) {
+        Some(&Ranged::Attack { damage, range }) => Some((damage, range)),
+        _ => None,
+    };
+
+    if ranged_attack.is_some() && distance < KEEP_DISTANCE as f32 && back_away(monster_id, player_pos, &game.map, objects) {
+        // Backed off out of the player's reach
+    } else if let Some((damage, range)) = ranged_attack
+        && player_alive
+        && matches!(line_of_flight(objects.pos(monster_id), player_pos, range, &game.map, objects), Flight::Hit(PLAYER))
+    {
+        shoot(monster_id, PLAYER, damage, game, objects);
+        spend_energy(monster_id, SHOOT_COST, objects);
+    } else if distance >= 2.0 {
+        // Move towards player if far away, going around anything in the way
+        chase(monster_id, player_pos.0, player_pos.1, &game.map, objects);
+    } else if player_alive {
+        // Close enough, attack! (if the player is still alive.)
+        attack(monster_id, PLAYER, game, objects);
+        spend_energy(monster_id, ATTACK_COST, objects);
+    }
+
+    Ai::Tracking {
+        last_seen: objects.pos(PLAYER),
+        turns_left: MONSTER_MEMORY_TURNS,
+    }
+}
+
+/// Step straight away from `from`, if that way is open. Returns whether the monster moved
+fn back_away(id: ObjectId, from: (i32, i32), map: &Map, objects: &mut Objects) -> bool {
+    let (x, y) = objects.pos(id);
+    let (dx, dy) = ((x - from.0).signum(), (y - from.1).signum());
+    if is_blocked(x + dx, y + dy, map, objects) {
+        return false;
+    }
+    move_by(id, dx, dy, map, objects);
+    spend_energy(id, MOVE_COST, objects);
+    true
+}
+
+/// Make a monster that was attacked from afar go looking for whoever did it
+pub fn alert(monster_id: ObjectId, attacker_pos: (i32, i32), objects: &mut Objects) {
+    if let Some(ai) = objects.ais.get_mut(monster_id) && ai.is_unaware() {
+        *ai = Ai::Tracking {
+            last_seen: attacker_pos,
+            turns_left: MONSTER_MEMORY_TURNS,
+        };
+    }
+}
+
+/// Somewhere nearby to stroll to, or where the monster already is if nowhere nearby is open
+fn wander_destination(from: (i32, i32), map: &Map, rng: &mut GameRng) -> (i32, i32) {
+    for _ in 0..WANDER_ATTEMPTS {
+        let x = (from.0 + rng.gen_range(-WANDER_RADIUS, WANDER_RADIUS + 1)).clamp(0, MAP_WIDTH - 1);
+        let y = (from.1 + rng.gen_range(-WANDER_RADIUS, WANDER_RADIUS + 1)).clamp(0, MAP_HEIGHT - 1);
+        if map[x as usize][y as usize].is_walkable() {
+            return (x, y);
+        }
+    }
+    from
+}
+
+fn ai_confused(monster_id: ObjectId, game: &mut Game, objects: &mut Objects) {
+    // Move in random direction
+    let (dx, dy) = (game.rng.gen_range(-1, 2), game.rng.gen_range(-1, 2));
+    move_by(monster_id, dx, dy, &game.map, objects);
+    spend_energy(monster_id, MOVE_COST, objects);
+}
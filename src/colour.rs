@@ -1,21 +1,72 @@
-use serde::{Serialize, Deserialize};
-use tcod::colors::Color;
-
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct SerializableColour {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-}
-
-impl From<Color> for SerializableColour {
-    fn from(color: Color) -> Self {
-        SerializableColour { r: color.r, g: color.g, b: color.b }
-    }
-}
-
-impl From<SerializableColour> for Color {
-    fn from(scolor: SerializableColour) -> Self {
-        Color::new(scolor.r, scolor.g, scolor.b)
-    }
-}
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerializableColour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// Human readable formats (JSON) get `{ r, g, b }`, binary ones a single 0xRRGGBB number
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ColourRepr {
+    Channels { r: u8, g: u8, b: u8 },
+    Packed(u32),
+}
+
+impl Serialize for SerializableColour {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let SerializableColour { r, g, b } = *self;
+        if serializer.is_human_readable() {
+            ColourRepr::Channels { r, g, b }.serialize(serializer)
+        } else {
+            ColourRepr::Packed(((r as u32) << 16) | ((g as u32) << 8) | b as u32).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializableColour {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match ColourRepr::deserialize(deserializer)? {
+            ColourRepr::Channels { r, g, b } => SerializableColour { r, g, b },
+            ColourRepr::Packed(rgb) => SerializableColour::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8),
+        })
+    }
+}
+
+impl SerializableColour {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        SerializableColour { r, g, b }
+    }
+}
+
+
+//-----------------------------//
+// PALETTE                     //
+//-----------------------------//
+// Same values as the libtcod palette, so the game core doesn't depend on tcod
+pub const BLACK: SerializableColour = SerializableColour::new(0, 0, 0);
+pub const WHITE: SerializableColour = SerializableColour::new(255, 255, 255);
+pub const LIGHT_GREY: SerializableColour = SerializableColour::new(159, 159, 159);
+
+pub const RED: SerializableColour = SerializableColour::new(255, 0, 0);
+pub const LIGHT_RED: SerializableColour = SerializableColour::new(255, 63, 63);
+pub const DARK_RED: SerializableColour = SerializableColour::new(191, 0, 0);
+pub const DARKER_RED: SerializableColour = SerializableColour::new(127, 0, 0);
+
+pub const ORANGE: SerializableColour = SerializableColour::new(255, 127, 0);
+
+pub const YELLOW: SerializableColour = SerializableColour::new(255, 255, 0);
+pub const LIGHT_YELLOW: SerializableColour = SerializableColour::new(255, 255, 63);
+
+
+pub const GREEN: SerializableColour = SerializableColour::new(0, 255, 0);
+pub const LIGHT_GREEN: SerializableColour = SerializableColour::new(63, 255, 63);
+
+pub const LIGHT_CYAN: SerializableColour = SerializableColour::new(63, 255, 255);
+pub const SKY: SerializableColour = SerializableColour::new(0, 191, 255);
+pub const LIGHT_BLUE: SerializableColour = SerializableColour::new(63, 63, 255);
+
+pub const VIOLET: SerializableColour = SerializableColour::new(127, 0, 255);
+pub const LIGHT_VIOLET: SerializableColour = SerializableColour::new(159, 63, 255);
@@ -1,105 +1,237 @@
-use tcod::colors::*;
-
-use crate::*;
-
-impl Object {
-    pub fn attack(&mut self, target: &mut Object, game: &mut Game) {
-        // A simple formula for attack damage
-        let damage = self.fighter.map_or(0, |f| f.base_power) - target.fighter.map_or(0, |f| f.base_defense);
-
-        if damage > 0 {
-            // Take damage
-            game.messages.add(format!("{} attacks {} for {} damage.", self.name, target.name, damage), WHITE.into());
-            
-            if let Some(xp) = target.take_damage(damage, game) {
-                // Give XP to player
-                self.fighter.as_mut().unwrap().xp += xp;
-            }
-        } else {
-            game.messages.add(format!("{} attacks {} but it has no effect!", self.name, target.name), WHITE.into());
-        }
-    }
-
-    pub fn take_damage(&mut self, damage: i32, game: &mut Game) -> Option<i32> {
-        // Apply damage if possible
-        if let Some(fighter) = self.fighter.as_mut() {
-            if damage > 0 { fighter.hp -= damage; }
-        }
-
-        // Check for death, call function if needed
-        if let Some(fighter) = self.fighter {
-            if fighter.hp <= 0 {
-                self.alive = false;
-                fighter.on_death.callback(self, game);
-                return Some(fighter.xp);
-            }
-        }
-        None
-    }
-
-    /// Heal by the given amount, without going over the maximum
-    pub fn heal(&mut self, amount: i32, game: &Game) {  
-        let max_hp = self.max_hp(game);  
-        if let Some(ref mut fighter) = self.fighter {
-            fighter.hp += amount;
-            if fighter.hp > max_hp {  
-                fighter.hp = max_hp;  
-            }
-        }
-    }
-}
-
-pub fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, objects: &mut[Object]) {
-    // Coords to move/attack to
-    let x= objects[PLAYER].x + dx;
-    let y= objects[PLAYER].y + dy;
-
-    // Try to find an attackable object there
-    let target_id = objects.iter().position(|object| object.fighter.is_some() && object.pos() == (x, y));
-
-    // Attack if target_id != None
-    match target_id {
-        Some(target_id) => {
-            let (player, target) = mut_two(PLAYER, target_id, objects);
-            player.attack(target, game);
-        }
-        None => {
-            move_by(PLAYER, dx, dy, &game.map, objects);
-        }
-    }
-}
-
-pub fn closest_monster(tcod: &Tcod, objects: &[Object], max_range: i32) -> Option<usize> {
-    let mut closest_enemy = None;
-    let mut closest_dist = (max_range + 1) as f32;  // Start with slightly more than max range
-
-    for (id, object) in objects.iter().enumerate() {
-        if (id != PLAYER) && object.fighter.is_some() && object.ai.is_some() && tcod.fov.is_in_fov(object.x, object.y) {
-            // Calculate distance to object
-            let dist = objects[PLAYER].distance_to(object);
-            if dist < closest_dist {
-                // Current enemy is closer
-                closest_enemy = Some(id);
-                closest_dist = dist;
-            }
-        }
-    }
-
-    closest_enemy
-}
-
-pub fn target_monster(tcod: &mut Tcod, game: &mut Game, objects: &[Object], max_range: Option<f32>) -> Option<usize> {
-    loop {
-        match target_tile(tcod, game, objects, max_range) {
-            Some((x, y)) => {
-                // Return the first clicked monster, otherwise continue looping
-                for (id, obj) in objects.iter().enumerate() {
-                    if obj.pos() == (x, y) && obj.fighter.is_some() && id != PLAYER {
-                        return Some(id);
-                    }
-                }
-            }
-            None => return None,
-        }
-    }
-}
\ No newline at end of file
+use crate::*;
+
+pub fn attack(attacker: ObjectId, target: ObjectId, game: &mut Game, objects: &mut Objects) {
+    let dice = melee_dice(attacker, objects);
+    resolve_attack(attacker, target, dice, false, game, objects);
+}
+
+/// Attack a target that hasn't noticed the attacker. It can't get out of the way, and takes extra damage
+pub fn ambush(attacker: ObjectId, target: ObjectId, game: &mut Game, objects: &mut Objects) {
+    game.messages.add(format!("{} catches {} unaware!", objects.names[attacker], objects.names[target]), LIGHT_GREEN);
+    let dice = melee_dice(attacker, objects);
+    resolve_attack(attacker, target, dice, true, game, objects);
+}
+
+/// Hit a target from afar, rolling `dice` for the damage. Shots at a target that hasn't noticed the
+/// attacker count as an ambush too
+pub fn shoot(attacker: ObjectId, target: ObjectId, dice: Dice, game: &mut Game, objects: &mut Objects) {
+    let ambushed = objects.ais.get(target).is_some_and(Ai::is_unaware);
+    if ambushed {
+        game.messages.add(format!("{} catches {} unaware!", objects.names[attacker], objects.names[target]), LIGHT_GREEN);
+    }
+    resolve_attack(attacker, target, dice, ambushed, game, objects);
+}
+
+/// Weapon (or bare hands) plus the attacker's power
+fn melee_dice(attacker: ObjectId, objects: &Objects) -> Dice {
+    objects.melee_damage(attacker).plus(objects.power(attacker))
+}
+
+/// Chance out of 100 for the attacker to get past the target's evasion. Its defense only counts
+/// once the attack has hit
+fn hit_chance(attacker: ObjectId, target: ObjectId, objects: &Objects) -> i32 {
+    let chance = BASE_HIT_CHANCE + (objects.power(attacker) - objects.evasion(target)) * HIT_CHANCE_PER_POINT;
+    chance.clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE)
+}
+
+/// Roll to hit, then for damage less the target's armour, and explain it all in the combat log.
+/// Ambushes always hit, and critical hits roll the damage as if they were several
+fn resolve_attack(attacker: ObjectId, target: ObjectId, dice: Dice, ambushed: bool, game: &mut Game, objects: &mut Objects) {
+    let hit_chance = hit_chance(attacker, target, objects);
+    let to_hit = game.rng.gen_range(1, 101);
+    let critical = to_hit <= CRIT_CHANCE;
+    let mut log = format!("{} attacks {}: rolled {} to hit, needing {} or less", objects.names[attacker], objects.names[target], to_hit, hit_chance);
+
+    if !ambushed && !critical && to_hit > hit_chance {
+        game.combat_log.add(format!("{}. Miss.", log), LIGHT_GREY);
+        game.messages.add(format!("{} attacks {} but misses.", objects.names[attacker], objects.names[target]), WHITE);
+        return;
+    }
+    if ambushed {
+        log.push_str(", but the target was caught unaware");
+    }
+
+    let rolls = if critical { CRIT_MULTIPLIER } else { 1 };
+    let rolled: i32 = (0..rolls).map(|_| dice.roll(&mut game.rng)).sum();
+    let armour = objects.defense(target);
+    let mut damage = rolled - armour;
+    if critical {
+        log.push_str(&format!(". Critical! {} rolled {} times for {}", dice, rolls, rolled));
+    } else {
+        log.push_str(&format!(". {} rolled {}", dice, rolled));
+    }
+    log.push_str(&format!(", less {} armour", armour));
+    if ambushed {
+        damage *= AMBUSH_DAMAGE_MULTIPLIER;
+        log.push_str(&format!(", times {} for the ambush", AMBUSH_DAMAGE_MULTIPLIER));
+    }
+    game.combat_log.add(format!("{}: {} damage.", log, damage.max(0)), LIGHT_GREY);
+
+    hit(attacker, target, damage, critical, game, objects);
+}
+
+fn hit(attacker: ObjectId, target: ObjectId, damage: i32, critical: bool, game: &mut Game, objects: &mut Objects) {
+    if damage > 0 {
+        // Take damage
+        if critical {
+            game.messages.add(format!("{} critically hits {} for {} damage!", objects.names[attacker], objects.names[target], damage), LIGHT_RED);
+        } else {
+            game.messages.add(format!("{} attacks {} for {} damage.", objects.names[attacker], objects.names[target], damage), WHITE);
+        }
+
+        if let Some(xp) = take_damage(target, damage, game, objects) {
+            // Give XP to the attacker
+            if let Some(fighter) = objects.fighters.get_mut(attacker) {
+                fighter.xp += xp;
+            }
+        } else if let Some(effect) = objects.fighters.get(attacker).and_then(|f| f.on_hit) {
+            add_status(target, effect, attacker, game, objects);
+        }
+    } else {
+        game.messages.add(format!("{} attacks {} but it has no effect!", objects.names[attacker], objects.names[target]), WHITE);
+    }
+}
+
+/// Returns the XP the object was worth, if this killed it
+pub fn take_damage(id: ObjectId, damage: i32, game: &mut Game, objects: &mut Objects) -> Option<i32> {
+    // Apply damage if possible
+    if let Some(fighter) = objects.fighters.get_mut(id)
+        && damage > 0
+    {
+        fighter.hp -= damage;
+    }
+
+    // Check for death, call function if needed
+    if let Some(&fighter) = objects.fighters.get(id)
+        && fighter.hp <= 0
+    {
+        fighter.on_death.callback(id, game, objects);
+        return Some(fighter.xp);
+    }
+    None
+}
+
+/// Heal by the given amount, without going over the maximum
+pub fn heal(id: ObjectId, amount: i32, objects: &mut Objects) {
+    let max_hp = objects.max_hp(id);
+    if let Some(fighter) = objects.fighters.get_mut(id) {
+        fighter.hp += amount;
+        if fighter.hp > max_hp {
+            fighter.hp = max_hp;
+        }
+    }
+}
+
+/// Returns how much energy it took, which depends on whether the player moved or attacked
+pub fn player_move_or_attack(mut dx: i32, mut dy: i32, game: &mut Game, objects: &mut Objects) -> i32 {
+    // Confusion sends the player stumbling about, sometimes nowhere at all
+    if objects.has_status(PLAYER, StatusKind::Confused) {
+        (dx, dy) = (game.rng.gen_range(-1, 2), game.rng.gen_range(-1, 2));
+        if (dx, dy) == (0, 0) {
+            game.noise = WAIT_NOISE;
+            return WAIT_COST;
+        }
+    }
+
+    // Coords to move/attack to
+    let (x, y) = objects.pos(PLAYER);
+    let (x, y) = (x + dx, y + dy);
+
+    // Try to find an attackable object there
+    let target_id = objects.at(x, y).find(|&id| objects.fighters.contains(id));
+
+    // Attack if target_id != None
+    match target_id {
+        Some(target_id) => {
+            game.noise = FIGHT_NOISE;
+            if objects.ais.get(target_id).is_some_and(Ai::is_unaware) {
+                ambush(PLAYER, target_id, game, objects);
+            } else {
+                attack(PLAYER, target_id, game, objects);
+            }
+            ATTACK_COST
+        }
+        None => {
+            game.noise = WALK_NOISE;
+            move_by(PLAYER, dx, dy, &game.map, objects);
+            MOVE_COST
+        }
+    }
+}
+
+pub fn closest_monster(game: &Game, objects: &Objects, max_range: i32) -> Option<ObjectId> {
+    let mut closest_enemy = None;
+    let mut closest_dist = (max_range + 1) as f32;  // Start with slightly more than max range
+
+    for (id, &(x, y)) in objects.positions.iter() {
+        if !objects.players.contains(id) && objects.fighters.contains(id) && objects.ais.contains(id) && game.fov.is_in_fov(x, y) {
+            // Calculate distance to object
+            let dist = objects.distance(PLAYER, x, y);
+            if dist < closest_dist {
+                // Current enemy is closer
+                closest_enemy = Some(id);
+                closest_dist = dist;
+            }
+        }
+    }
+
+    closest_enemy
+}
+
+pub fn target_monster(frontend: &mut dyn Frontend, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<ObjectId> {
+    loop {
+        match target_tile(frontend, game, objects, max_range) {
+            Some((x, y)) => {
+                // Return the first clicked monster, otherwise continue looping
+                if let Some(id) = objects.at(x, y).find(|&id| objects.fighters.contains(id) && !objects.players.contains(id)) {
+                    return Some(id);
+                }
+            }
+            None => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fighter(objects: &mut Objects, power: i32, defense: i32, evasion: i32) -> ObjectId {
+        let id = objects.spawn("fighter");
+        objects.fighters.insert(id, Fighter {
+            base_max_hp: 10,
+            hp: 10,
+            base_defense: defense,
+            base_evasion: evasion,
+            base_power: power,
+            xp: 0,
+            on_death: DeathCallback::Monster,
+            on_hit: None,
+            speed: NORMAL_SPEED,
+            energy: 0,
+            level: 1,
+        });
+        id
+    }
+
+    #[test]
+    fn armour_does_not_make_attacks_miss() {
+        let mut objects = Objects::default();
+        let attacker = fighter(&mut objects, 4, 0, 0);
+        let unarmoured = fighter(&mut objects, 0, 0, 2);
+        let armoured = fighter(&mut objects, 0, 10, 2);
+
+        assert_eq!(hit_chance(attacker, armoured, &objects), hit_chance(attacker, unarmoured, &objects));
+        assert_eq!(hit_chance(attacker, armoured, &objects), BASE_HIT_CHANCE + 2 * HIT_CHANCE_PER_POINT);
+    }
+
+    #[test]
+    fn evasion_makes_attacks_miss() {
+        let mut objects = Objects::default();
+        let attacker = fighter(&mut objects, 4, 0, 0);
+        let slow = fighter(&mut objects, 0, 0, 0);
+        let nimble = fighter(&mut objects, 0, 0, 3);
+
+        assert_eq!(hit_chance(attacker, slow, &objects) - hit_chance(attacker, nimble, &objects), 3 * HIT_CHANCE_PER_POINT);
+    }
+}
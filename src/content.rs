@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+use crate::*;
+
+const MONSTERS_FILE: &str = "assets/monsters.json";
+const ITEMS_FILE: &str = "assets/items.json";
+const SPAWN_TABLES_FILE: &str = "assets/spawn_tables.json";
+const AFFIXES_FILE: &str = "assets/affixes.json";
+
+static CONTENT: OnceLock<Content> = OnceLock::new();
+
+/// Game data loaded from `assets/`, so creatures and items can be added without touching the code
+pub struct Content {
+    pub monsters: Vec<MonsterDef>,
+    pub items: Vec<ItemDef>,
+    pub spawn_tables: SpawnTables,
+    pub affixes: AffixTables,
+}
+
+/// One kind of monster, as found in `MONSTERS_FILE`
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterDef {
+    pub name: String,
+    pub glyph: char,
+    pub colour: SerializableColour,
+    pub max_hp: i32,
+    pub defense: i32,
+    #[serde(default)]
+    pub evasion: i32,
+    pub power: i32,
+    pub xp: i32,
+    #[serde(default)]
+    pub ai: AiKind,
+    pub senses: Senses,
+    #[serde(default)]
+    pub ranged_attack: Option<RangedAttack>,    // Monsters with one keep their distance
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,   // Put on the player whenever it does damage
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    #[serde(default)]
+    pub gear: Vec<GearDef>,     // What it may be carrying when placed
+    pub spawn_weight: Vec<Transition>,  // How likely it is to be picked, by depth
+}
+
+/// An item a monster starts out with, some of the time
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GearDef {
+    pub item: String,
+    #[serde(default = "always")]
+    pub chance: u32,    // Percentage of monsters of the kind that have it
+}
+
+/// A monster's own way of hurting the player from afar, such as a sling or spit
+#[derive(Clone, Copy, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RangedAttack {
+    pub damage: Dice,
+    pub range: i32,
+}
+
+/// How a monster behaves when it is first placed
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+pub enum AiKind {
+    /// Idles until it notices the player, and is now and then found asleep
+    #[default]
+    Basic,
+    /// Always found asleep
+    Sleeper,
+    /// Roams about from the start
+    Wanderer,
+}
+
+/// One kind of item, as found in `ITEMS_FILE`
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDef {
+    pub name: String,
+    pub glyph: char,
+    pub colour: SerializableColour,
+    pub effect: Item,
+    #[serde(default)]
+    pub equipment: Option<EquipmentDef>,    // Needed by, and only by, `Item::Equip`
+    #[serde(default)]
+    pub ranged: Option<Ranged>,
+    #[serde(default = "one")]
+    pub count: u32,     // How many are found together. Only items that stack come more than one at a time
+    #[serde(default)]
+    pub spawn_weight: Vec<Transition>,  // Left out for items that are only ever given, never found
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn always() -> u32 {
+    100
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+/// Where an item is worn and what it gives. Bonuses left out are 0
+#[derive(Clone, Copy, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EquipmentDef {
+    pub slot: Slot,
+    #[serde(default)]
+    pub max_hp_bonus: i32,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+    #[serde(default)]
+    pub evasion_bonus: i32,
+    #[serde(default)]
+    pub damage: Option<Dice>,
+    #[serde(default)]
+    pub two_handed: bool,
+}
+
+/// How much each room gets at most, by depth
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnTables {
+    pub monsters_per_room: Vec<Transition>,
+    pub items_per_room: Vec<Transition>,
+}
+
+/// The prefixes and suffixes equipment can be found with, as found in `AFFIXES_FILE`
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AffixTables {
+    pub prefixes: Vec<AffixDef>,
+    pub suffixes: Vec<AffixDef>,
+}
+
+/// One way a piece of equipment can turn out better than others of its kind. Bonuses left out are 0
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AffixDef {
+    pub name: String,
+    #[serde(default)]
+    pub slots: Vec<Slot>,   // Only found on equipment worn in these, or on any if left out
+    #[serde(default = "one")]
+    pub min_level: u32,     // The shallowest depth it's found at
+    #[serde(default)]
+    pub max_hp_bonus: i32,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+    #[serde(default)]
+    pub evasion_bonus: i32,
+}
+
+/// A content file that couldn't be read or doesn't make sense
+#[derive(Debug)]
+pub struct ContentError {
+    file: &'static str,
+    message: String,
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+impl std::error::Error for ContentError {}
+
+/// Load and check all content. Called once at startup, so mistakes show up straight away
+pub fn load_content() -> Result<(), ContentError> {
+    let items: Vec<ItemDef> = load_file(ITEMS_FILE)?;
+    validate_items(&items).map_err(|message| ContentError { file: ITEMS_FILE, message })?;
+
+    // Monsters come second, as their gear refers to items
+    let monsters: Vec<MonsterDef> = load_file(MONSTERS_FILE)?;
+    validate_monsters(&monsters, &items).map_err(|message| ContentError { file: MONSTERS_FILE, message })?;
+
+    let spawn_tables: SpawnTables = load_file(SPAWN_TABLES_FILE)?;
+    validate_table(&spawn_tables.monsters_per_room)
+        .and_then(|_| validate_table(&spawn_tables.items_per_room))
+        .map_err(|message| ContentError { file: SPAWN_TABLES_FILE, message })?;
+
+    let affixes: AffixTables = load_file(AFFIXES_FILE)?;
+    validate_affixes(&affixes.prefixes)
+        .and_then(|_| validate_affixes(&affixes.suffixes))
+        .map_err(|message| ContentError { file: AFFIXES_FILE, message })?;
+
+    // Loading twice is harmless, the first load stays
+    let _ = CONTENT.set(Content { monsters, items, spawn_tables, affixes });
+    Ok(())
+}
+
+/// The loaded content. `load_content` must have succeeded first
+pub fn content() -> &'static Content {
+    CONTENT.get().expect("Content used before it was loaded")
+}
+
+fn load_file<T: serde::de::DeserializeOwned>(file: &'static str) -> Result<T, ContentError> {
+    let text = fs::read_to_string(file).map_err(|e| ContentError { file, message: e.to_string() })?;
+    serde_json::from_str(&text).map_err(|e| ContentError { file, message: e.to_string() })
+}
+
+fn validate_monsters(monsters: &[MonsterDef], items: &[ItemDef]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for monster in monsters {
+        let problem = if monster.name.is_empty() {
+            Some("has no name".to_string())
+        } else if !names.insert(monster.name.as_str()) {
+            Some("is defined more than once".to_string())
+        } else if monster.max_hp <= 0 {
+            Some(format!("needs a positive max_hp, not {}", monster.max_hp))
+        } else if monster.xp < 0 || monster.defense < 0 || monster.evasion < 0 || monster.power < 0 {
+            Some("can't have negative xp, defense, evasion or power".to_string())
+        } else if monster.speed <= 0 {
+            Some(format!("needs a positive speed, not {}", monster.speed))
+        } else if monster.senses.sight_radius < 0 || monster.senses.perception < 0 || monster.senses.hearing < 0 {
+            Some("can't have negative senses".to_string())
+        } else if monster.ranged_attack.is_some_and(|attack| attack.damage.average() < 0 || attack.range <= 0) {
+            Some("needs a ranged attack with a positive range and no negative damage".to_string())
+        } else if let Some(problem) = monster.on_hit.and_then(status_problem) {
+            Some(format!("has an on_hit status that {}", problem))
+        } else if let Some(gear) = monster.gear.iter().find(|gear| !items.iter().any(|item| item.name == gear.item)) {
+            Some(format!("has gear \"{}\" that isn't a known item", gear.item))
+        } else if monster.gear.iter().any(|gear| gear.chance == 0 || gear.chance > 100) {
+            Some("needs gear chances from 1 to 100".to_string())
+        } else {
+            validate_table(&monster.spawn_weight).err()
+        };
+
+        if let Some(problem) = problem {
+            return Err(format!("monster \"{}\" {}", monster.name, problem));
+        }
+    }
+
+    let tables: Vec<_> = monsters.iter().map(|monster| monster.spawn_weight.as_slice()).collect();
+    check_every_depth_spawns("monster", &tables)
+}
+
+fn validate_items(items: &[ItemDef]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for item in items {
+        let problem = if item.name.is_empty() {
+            Some("has no name".to_string())
+        } else if !names.insert(item.name.as_str()) {
+            Some("is defined more than once".to_string())
+        } else if (item.effect == Item::Equip) != item.equipment.is_some() {
+            Some("needs equipment if, and only if, its effect is Equip".to_string())
+        } else if item.equipment.is_some_and(|e| e.two_handed && e.slot != Slot::MainHand) {
+            Some("can only be two-handed in the MainHand slot".to_string())
+        } else if let Some(problem) = ranged_problem(item, items) {
+            Some(problem)
+        } else if let Item::Drink(effect) | Item::Afflict(effect) = item.effect
+            && let Some(problem) = status_problem(effect)
+        {
+            Some(format!("has a status that {}", problem))
+        } else if item.count == 0 || (item.count > 1 && !item.effect.stacks()) {
+            Some(format!("can't be found {} at a time", item.count))
+        } else {
+            validate_table(&item.spawn_weight).err()
+        };
+
+        if let Some(problem) = problem {
+            return Err(format!("item \"{}\" {}", item.name, problem));
+        }
+    }
+
+    if !items.iter().any(|item| item.name == STARTING_WEAPON && item.equipment.is_some()) {
+        return Err(format!("needs a \"{}\" to equip the player with", STARTING_WEAPON));
+    }
+
+    // Every potion needs an appearance of its own
+    let potions = items.iter().filter(|item| is_potion(item.effect)).count();
+    if potions > POTION_APPEARANCES.len() {
+        return Err(format!("has {} potions, but they can only look {} different ways", potions, POTION_APPEARANCES.len()));
+    }
+
+    let tables: Vec<_> = items.iter().map(|item| item.spawn_weight.as_slice()).collect();
+    check_every_depth_spawns("item", &tables)
+}
+
+fn validate_affixes(affixes: &[AffixDef]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for affix in affixes {
+        let problem = if affix.name.is_empty() {
+            Some("has no name")
+        } else if !names.insert(affix.name.as_str()) {
+            Some("is defined more than once")
+        } else if affix.min_level == 0 {
+            Some("needs a min_level of 1 or more")
+        } else if affix.max_hp_bonus <= 0 && affix.power_bonus <= 0 && affix.defense_bonus <= 0 && affix.evasion_bonus <= 0 {
+            Some("needs to give some bonus")
+        } else {
+            None
+        };
+
+        if let Some(problem) = problem {
+            return Err(format!("affix \"{}\" {}", affix.name, problem));
+        }
+    }
+    Ok(())
+}
+
+/// What, if anything, is wrong with how an item works at a distance
+fn ranged_problem(item: &ItemDef, items: &[ItemDef]) -> Option<String> {
+    let is_missile = matches!(item.ranged, Some(Ranged::Ammo { .. } | Ranged::Thrown { .. }));
+    if (item.effect == Item::Missile) != is_missile {
+        return Some("needs Ammo or Thrown if, and only if, its effect is Missile".into());
+    }
+
+    match &item.ranged {
+        Some(Ranged::Launcher { ammo, power, range }) => {
+            let has_ammo = items.iter().any(|other| matches!(&other.ranged, Some(Ranged::Ammo { kind, .. }) if kind == ammo));
+            if item.effect != Item::Equip {
+                Some("must be equipped to shoot, so needs the Equip effect".into())
+            } else if !has_ammo {
+                Some(format!("shoots \"{}\", but no item is that kind of ammo", ammo))
+            } else if *power < 0 || *range <= 0 {
+                Some("needs a positive range and no negative power".into())
+            } else {
+                None
+            }
+        }
+        Some(Ranged::Ammo { damage, .. }) if damage.average() < 0 => Some("can't do negative damage".into()),
+        Some(Ranged::Thrown { damage, range }) if damage.average() < 0 || *range <= 0 => {
+            Some("needs a positive range and no negative damage".into())
+        }
+        Some(Ranged::Attack { .. }) => Some("can't have a monster's ranged Attack".into()),
+        _ => None,
+    }
+}
+
+/// What, if anything, is wrong with a status effect
+fn status_problem(effect: StatusEffect) -> Option<String> {
+    if effect.turns <= 0 {
+        Some(format!("needs a positive number of turns, not {}", effect.turns))
+    } else if effect.magnitude < 0 {
+        Some("can't have a negative magnitude".into())
+    } else {
+        None
+    }
+}
+
+/// Tables must go deeper with every entry, or later entries would never be reached
+fn validate_table(table: &[Transition]) -> Result<(), String> {
+    if table.windows(2).any(|pair| pair[0].level >= pair[1].level) {
+        return Err("has depths out of order".into());
+    }
+    Ok(())
+}
+
+/// Every depth needs something to spawn. Weights only change at a transition, so checking those is enough
+fn check_every_depth_spawns(kind: &str, spawn_weights: &[&[Transition]]) -> Result<(), String> {
+    let depths = spawn_weights.iter().flat_map(|table| table.iter().map(|t| t.level));
+    for depth in depths.chain([1]) {
+        if spawn_weights.iter().all(|table| from_dungeon_level(table, depth) == 0) {
+            return Err(format!("no {} can spawn at depth {}", kind, depth));
+        }
+    }
+    Ok(())
+}
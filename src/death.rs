@@ -1,47 +1,56 @@
-use tcod::colors::*;
-
-use crate::*;
-
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub enum DeathCallback {
-    Player,
-    Monster,
-}
-
-impl DeathCallback {
-    pub fn callback(self, object: &mut Object, game: &mut Game) {
-        use DeathCallback::*;
-        let callback = match self {
-            Player => player_death,
-            Monster => monster_death,
-        };
-        callback(object, game);
-    }
-}
-
-fn player_death(player: &mut Object, game: &mut Game) {
-    // Game ended
-    game.messages.add("You Died!", RED.into());
-
-    // For added effect, turn player into corpse
-    player.char = '%';
-    player.colour = DARK_RED.into();
-}
-
-fn monster_death(monster: &mut Object, game: &mut Game) {
-    // Transform into non-blocking, non-attacking, non-moving corpse
-    game.messages.add(
-        format!(
-            "{} is dead! You gain {} experience points.",
-            monster.name,
-            monster.fighter.unwrap().xp
-        ),
-        ORANGE.into(),
-    );
-    monster.char = '%';
-    monster.colour = DARKER_RED.into();
-    monster.blocks = false;
-    monster.fighter = None;
-    monster.ai = None;
-    monster.name = format!("remains of {}", monster.name);
-} 
\ No newline at end of file
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+impl DeathCallback {
+    pub fn callback(self, id: ObjectId, game: &mut Game, objects: &mut Objects) {
+        use DeathCallback::*;
+        let callback = match self {
+            Player => player_death,
+            Monster => monster_death,
+        };
+        callback(id, game, objects);
+    }
+}
+
+fn player_death(player: ObjectId, game: &mut Game, objects: &mut Objects) {
+    // Game ended
+    game.messages.add("You Died!", RED);
+
+    // For added effect, turn player into corpse
+    let renderable = &mut objects.renderables[player];
+    renderable.char = '%';
+    renderable.colour = DARK_RED;
+}
+
+fn monster_death(monster: ObjectId, game: &mut Game, objects: &mut Objects) {
+    // Transform into non-blocking, non-attacking, non-moving corpse
+    game.messages.add(
+        format!(
+            "{} is dead! You gain {} experience points.",
+            objects.names[monster],
+            objects.fighters[monster].xp
+        ),
+        ORANGE,
+    );
+    let renderable = &mut objects.renderables[monster];
+    renderable.char = '%';
+    renderable.colour = DARKER_RED;
+    objects.blockers.remove(monster);
+    objects.fighters.remove(monster);
+    objects.ais.remove(monster);
+    objects.names[monster] = format!("remains of {}", objects.names[monster]);
+
+    // Everything it carried falls where it died
+    let (x, y) = objects.pos(monster);
+    for item_id in objects.inventories.remove(monster).unwrap_or_default() {
+        if let Some(equipment) = objects.equipment.get_mut(item_id) {
+            equipment.equipped = false;
+        }
+        objects.set_pos(item_id, x, y);
+    }
+}
\ No newline at end of file
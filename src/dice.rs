@@ -0,0 +1,129 @@
+use crate::*;
+
+/// Damage rolled as `count` dice with `sides` sides each, plus a `bonus`. Written like "2d6+1",
+/// "d6" for a single die, or just a number for damage that never varies
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub bonus: i32,
+}
+
+impl Dice {
+    /// Damage that always comes out the same
+    pub fn flat(damage: i32) -> Self {
+        Dice { count: 0, sides: 0, bonus: damage }
+    }
+
+    pub fn roll(self, rng: &mut GameRng) -> i32 {
+        let rolled: i32 = (0..self.count).map(|_| rng.gen_range(1, self.sides as i32 + 1)).sum();
+        rolled + self.bonus
+    }
+
+    /// The damage rolled on average, rounded down
+    pub fn average(self) -> i32 {
+        (self.count * (self.sides + 1)) as i32 / 2 + self.bonus
+    }
+
+    /// The same dice with `bonus` more added on
+    pub fn plus(self, bonus: i32) -> Self {
+        Dice { bonus: self.bonus + bonus, ..self }
+    }
+}
+
+impl TryFrom<String> for Dice {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        let invalid = || format!("\"{}\" isn't dice like \"1d6\" or \"2d4+1\"", text);
+        let Some((count, rest)) = text.split_once('d') else {
+            return text.trim().parse().map(Dice::flat).map_err(|_| invalid());
+        };
+
+        // The bonus keeps its sign, so "1d4-1" takes one off
+        let (sides, bonus) = match rest.find(['+', '-']) {
+            Some(sign) => (&rest[..sign], rest[sign..].trim_start_matches('+')),
+            None => (rest, "0"),
+        };
+        let dice = Dice {
+            count: match count.trim() {
+                "" => 1,
+                count => count.parse().map_err(|_| invalid())?,
+            },
+            sides: sides.trim().parse().map_err(|_| invalid())?,
+            bonus: bonus.trim().parse().map_err(|_| invalid())?,
+        };
+        if dice.sides == 0 {
+            return Err(invalid());
+        }
+        Ok(dice)
+    }
+}
+
+impl From<Dice> for String {
+    fn from(dice: Dice) -> String {
+        dice.to_string()
+    }
+}
+
+impl std::fmt::Display for Dice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.count, self.bonus) {
+            (0, bonus) => write!(f, "{}", bonus),
+            (count, 0) => write!(f, "{}d{}", count, self.sides),
+            (count, bonus) => write!(f, "{}d{}{:+}", count, self.sides, bonus),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Dice, String> {
+        Dice::try_from(text.to_string())
+    }
+
+    #[test]
+    fn dice_parse_from_text() {
+        assert_eq!(parse("2d4+1"), Ok(Dice { count: 2, sides: 4, bonus: 1 }));
+        assert_eq!(parse("1d4-1"), Ok(Dice { count: 1, sides: 4, bonus: -1 }));
+        assert_eq!(parse("d6"), Ok(Dice { count: 1, sides: 6, bonus: 0 }));
+        assert_eq!(parse(" 3 "), Ok(Dice::flat(3)));
+    }
+
+    #[test]
+    fn bad_dice_are_refused() {
+        for text in ["", "d", "2d", "xd6", "2d0", "1d6+", "1d6+x", "six"] {
+            assert!(parse(text).is_err(), "\"{}\" should not parse", text);
+        }
+    }
+
+    #[test]
+    fn dice_are_written_as_they_are_read() {
+        for text in ["2d4+1", "1d4-1", "1d6", "3"] {
+            assert_eq!(parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rolls_stay_within_the_dice() {
+        let dice = Dice { count: 2, sides: 4, bonus: 1 };
+        let mut rng = GameRng::new(1);
+        let rolls: Vec<i32> = (0..1000).map(|_| dice.roll(&mut rng)).collect();
+
+        assert!(rolls.iter().all(|roll| (3..=9).contains(roll)));
+        assert!(rolls.contains(&3) && rolls.contains(&9));
+        assert_eq!(Dice::flat(4).roll(&mut rng), 4);
+    }
+
+    #[test]
+    fn averages_round_down() {
+        assert_eq!(Dice { count: 2, sides: 6, bonus: 0 }.average(), 7);
+        assert_eq!(Dice { count: 1, sides: 4, bonus: 0 }.average(), 2);
+        assert_eq!(Dice { count: 1, sides: 6, bonus: 2 }.average(), 5);
+        assert_eq!(Dice::flat(3).plus(2).average(), 5);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::*;
+
+pub const DOWN_STAIRS: &str = "Down stairs";
+pub const UP_STAIRS: &str = "Up stairs";
+
+/// A floor the player has left, kept exactly as it was
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Level {
+    pub depth: u32,
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    pub map: Map,
+    pub objects: Vec<(ObjectId, (i32, i32))>,   // Where everything on the floor was. The objects stay in `Objects`
+}
+
+/// Every visited floor other than the one being played.
+/// The current floor lives in `Game::map` and the objects list
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct Dungeon {
+    levels: Vec<Level>,     // A list rather than a map by depth, as binary saves can't have number keys
+}
+
+impl Dungeon {
+    /// Put a floor away until the player comes back to it
+    pub fn store(&mut self, level: Level) {
+        self.levels.push(level);
+    }
+
+    /// Take a floor back out, or `None` if it was never visited
+    pub fn take(&mut self, depth: u32) -> Option<Level> {
+        let index = self.levels.iter().position(|level| level.depth == depth)?;
+        Some(self.levels.swap_remove(index))
+    }
+}
+
+/// Take whichever stairs the player is standing on
+pub fn take_stairs(game: &mut Game, objects: &mut Objects) {
+    let (x, y) = objects.pos(PLAYER);
+    let stairs = objects
+        .at(x, y)
+        .map(|id| objects.names[id].clone())
+        .find(|name| name == DOWN_STAIRS || name == UP_STAIRS);
+
+    match stairs.as_deref() {
+        Some(DOWN_STAIRS) => {
+            game.messages.add("You descend deeper into the dungeon...", RED);
+            change_level(game.dungeon_level + 1, game, objects);
+        }
+        Some(UP_STAIRS) => {
+            game.messages.add("You climb back up the stairs...", VIOLET);
+            change_level(game.dungeon_level - 1, game, objects);
+        }
+        _ => {}
+    }
+}
+
+/// Store the current floor and move the player to `depth`, generating it on the first visit.
+/// On a floor seen before the player arrives on the stairs leading back to the one they left
+fn change_level(depth: u32, game: &mut Game, objects: &mut Objects) {
+    let from = game.dungeon_level;
+    game.dungeon.store(Level {
+        depth: from,
+        map: std::mem::take(&mut game.map),
+        objects: objects.take_floor(),
+    });
+    game.dungeon_level = depth;
+
+    match game.dungeon.take(depth) {
+        Some(level) => {
+            game.map = level.map;
+            objects.restore_floor(level.objects);
+
+            let arrival = if depth < from { DOWN_STAIRS } else { UP_STAIRS };
+            let stairs = objects.positions.iter().find(|&(id, _)| objects.names[id] == arrival);
+            if let Some((_, &(x, y))) = stairs {
+                objects.set_pos(PLAYER, x, y);
+            }
+        }
+        None => {
+            // Only a new floor gives the chance to rest, or the stairs could be used to heal
+            game.messages.add("You take a moment to rest, and recover your strength.", VIOLET);
+            let heal_hp = objects.max_hp(PLAYER) / 2;
+            heal(PLAYER, heal_hp, objects);
+
+            game.map = make_map(objects, depth, &mut game.rng, &mut game.messages);
+            disguise_items(game, objects);
+        }
+    }
+
+    initialise_fov(game);
+    compute_player_fov(game, objects);
+}
@@ -0,0 +1,164 @@
+use crate::*;
+
+/// How special a piece of equipment is. Decides how many affixes it gets, and the colour it's shown in
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Rarity {
+    Common,
+    /// A prefix or a suffix
+    Magic,
+    /// Both a prefix and a suffix
+    Rare,
+    /// Both, and more enchanted than the depth allows
+    Legendary,
+}
+
+impl Rarity {
+    pub fn colour(self) -> SerializableColour {
+        match self {
+            Rarity::Common => WHITE,
+            Rarity::Magic => SKY,
+            Rarity::Rare => YELLOW,
+            Rarity::Legendary => ORANGE,
+        }
+    }
+}
+
+/// What made a piece of equipment better than others of its kind. Its bonuses are already part
+/// of the item's `Equipment`, this is kept to name it by
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Enchantment {
+    pub rarity: Rarity,
+    pub level: i32,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+}
+
+impl Enchantment {
+    /// The full name of an item called `name`, e.g. "+2 Flaming Sword of the Bear"
+    pub fn full_name(&self, name: &str) -> String {
+        let mut full_name = String::new();
+        if self.level > 0 {
+            full_name.push_str(&format!("+{} ", self.level));
+        }
+        if let Some(prefix) = &self.prefix {
+            full_name.push_str(&format!("{} ", prefix));
+        }
+        full_name.push_str(name);
+        if let Some(suffix) = &self.suffix {
+            full_name.push_str(&format!(" {}", suffix));
+        }
+        full_name
+    }
+}
+
+/// Roll how special a piece of equipment found at depth `level` turns out. Anything that isn't
+/// equipment stays as it is
+pub fn enchant(item_id: ObjectId, level: u32, rng: &mut GameRng, objects: &mut Objects) {
+    let Some(&equipment) = objects.equipment.get(item_id) else {
+        return;
+    };
+
+    let roll = rng.gen_range(0, 100) + level * RARITY_PER_LEVEL;
+    let rarity = match roll {
+        roll if roll >= LEGENDARY_THRESHOLD => Rarity::Legendary,
+        roll if roll >= RARE_THRESHOLD => Rarity::Rare,
+        roll if roll >= MAGIC_THRESHOLD => Rarity::Magic,
+        _ => Rarity::Common,
+    };
+
+    // Magic items get a prefix or, failing that, a suffix. Better ones get both
+    let affixes = &content().affixes;
+    let wants_prefix = match rarity {
+        Rarity::Common => false,
+        Rarity::Magic => rng.gen_range(0, 2) == 0,
+        Rarity::Rare | Rarity::Legendary => true,
+    };
+    let prefix = if wants_prefix { choose_affix(&affixes.prefixes, equipment.slot, level, rng) } else { None };
+    let wants_suffix = match rarity {
+        Rarity::Common => false,
+        Rarity::Magic => prefix.is_none(),
+        Rarity::Rare | Rarity::Legendary => true,
+    };
+    let suffix = if wants_suffix { choose_affix(&affixes.suffixes, equipment.slot, level, rng) } else { None };
+
+    let mut enchantment_level = rng.gen_range(0, level / ENCHANTMENT_DEPTH_STEP + 1) as i32;
+    if rarity == Rarity::Legendary {
+        enchantment_level += LEGENDARY_ENCHANTMENT_BONUS;
+    }
+    if rarity == Rarity::Common && enchantment_level == 0 {
+        return;
+    }
+
+    // Affixes and enchantment go straight into the bonuses. Enchantment adds to what the item is
+    // for: shot damage for launchers, attack for weapons, defense for armour, evasion for what
+    // only helps dodge and health for anything that only gives that
+    let enchanted = &mut objects.equipment[item_id];
+    for affix in prefix.iter().chain(&suffix) {
+        enchanted.max_hp_bonus += affix.max_hp_bonus;
+        enchanted.power_bonus += affix.power_bonus;
+        enchanted.defense_bonus += affix.defense_bonus;
+        enchanted.evasion_bonus += affix.evasion_bonus;
+    }
+    if let Some(Ranged::Launcher { power, .. }) = objects.ranged.get_mut(item_id) {
+        *power += enchantment_level;
+    } else if equipment.damage.is_none() && equipment.power_bonus == 0 && equipment.defense_bonus > 0 {
+        enchanted.defense_bonus += enchantment_level;
+    } else if equipment.damage.is_none() && equipment.power_bonus == 0 && equipment.evasion_bonus > 0 {
+        enchanted.evasion_bonus += enchantment_level;
+    } else if equipment.damage.is_none() && equipment.power_bonus == 0 && equipment.max_hp_bonus > 0 {
+        enchanted.max_hp_bonus += enchantment_level * ENCHANTMENT_HP;
+    } else {
+        enchanted.power_bonus += enchantment_level;
+    }
+
+    if rarity != Rarity::Common {
+        objects.renderables[item_id].colour = rarity.colour();
+    }
+    objects.enchantments.insert(item_id, Enchantment {
+        rarity,
+        level: enchantment_level,
+        prefix: prefix.map(|affix| affix.name.clone()),
+        suffix: suffix.map(|affix| affix.name.clone()),
+    });
+}
+
+/// One of the affixes that can be found on equipment worn in `slot` at this depth, if any can
+fn choose_affix<'a>(affixes: &'a [AffixDef], slot: Slot, level: u32, rng: &mut GameRng) -> Option<&'a AffixDef> {
+    let fitting: Vec<&AffixDef> = affixes
+        .iter()
+        .filter(|affix| affix.min_level <= level && (affix.slots.is_empty() || affix.slots.contains(&slot)))
+        .collect();
+    if fitting.is_empty() {
+        return None;
+    }
+    Some(fitting[rng.gen_range(0, fitting.len())])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launcher_enchantment_goes_into_shot_power() {
+        load_content().expect("Content should load");
+        let bow = content().items.iter().find(|item| item.name == "Bow").unwrap();
+        let Some(Ranged::Launcher { power: base_power, .. }) = bow.ranged else {
+            panic!("The Bow should be a launcher");
+        };
+        let mut objects = Objects::default();
+        let mut rng = GameRng::new(3);
+
+        // Deep down, where every bow has some enchantment to show for it
+        for _ in 0..50 {
+            let item_id = create_item(bow, &mut objects);
+            enchant(item_id, 30, &mut rng, &mut objects);
+
+            let level = objects.enchantments.get(item_id).map_or(0, |enchantment| enchantment.level);
+            let Some(Ranged::Launcher { power, .. }) = objects.ranged.get(item_id) else {
+                panic!("An enchanted Bow should still be a launcher");
+            };
+            assert_eq!(*power, base_power + level);
+        }
+    }
+}
@@ -1,127 +1,230 @@
-use tcod::colors::*;
-
-use crate::*;
-
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-/// An object that can be equipped, yielding bonuses
-pub struct Equipment {
-    pub slot: Slot,
-    pub equipped: bool,
-    pub max_hp_bonus: i32,
-    pub power_bonus: i32,
-    pub defense_bonus: i32,
-} 
-
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub enum Slot {
-    LeftHand,
-    RightHand,
-    Head,
-}
-
-impl Object {
-    /// Equip object and show message
-    pub fn equip(&mut self, messages: &mut Messages) {
-        if self.item.is_none() {
-            messages.add(format!("Cannot equip {:?} because it's not an Item.", self), RED.into());
-            return;
-        }
-
-        if let Some(ref mut equipment) = self.equipment {
-            if !equipment.equipped {
-                equipment.equipped = true;
-                messages.add(format!("Equipped {} on {}.", self.name, equipment.slot), LIGHT_GREEN.into());
-            }
-        } else {
-            messages.add(format!("Cannot equip {:?} because it's not Equipment.", self), RED.into());
-        }
-    }
-
-    pub fn unequip(&mut self, messages: &mut Messages) {
-        if self.item.is_none() {
-            messages.add(format!("Cannot unequip {:?} because it's not an Item.", self), RED.into());
-            return;
-        }
-        
-        if let Some(ref mut equipment) = self.equipment {
-            if equipment.equipped {
-                equipment.equipped = false;
-                messages.add(format!("Unequiped {} from {}.", self.name, equipment.slot), LIGHT_YELLOW.into());
-            }
-        } else {
-            messages.add(format!("Cannot unequip {:?} because it's not Equipment", self), RED.into());
-        }
-    }
-
-    /// Returns a list of all equipped items
-    pub fn get_all_equipped(&self, game: &Game) -> Vec<Equipment> {
-        if self.name == "player" {
-            game.inventory
-                .iter()
-                .filter(|item| item.equipment.map_or(false, |e| e.equipped))
-                .map(|item| item.equipment.unwrap())
-                .collect()
-        } else {
-            vec![]  // Other objects have no equipment
-        }
-    }
-}
-
-
-//-----------------------------//
-// EQUIPMENT BONUSES           //
-//-----------------------------//
-impl Object {
-    pub fn power(&self, game: &Game) -> i32 {
-        let base_power = self.fighter.map_or(0, |f| f.base_power);
-        let bonus: i32 = self.get_all_equipped(game).iter().map(|e| e.power_bonus).sum();
-        base_power + bonus
-    }
-
-    pub fn defense(&self, game: &Game) -> i32 {
-        let base_defense = self.fighter.map_or(0, |f| f.base_defense);
-        let bonus: i32 = self
-            .get_all_equipped(game)
-            .iter()
-            .map(|e| e.defense_bonus)
-            .sum();
-        base_defense + bonus
-    }
-}
-
-
-pub fn toggle_equipment(inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, _objects: &mut [Object]) -> UseResult {
-    let equipment = match game.inventory[inventory_id].equipment {
-        Some(equipment) => equipment,
-        None => return UseResult::Cancelled,
-    };
-
-    // If slot is already being used, unequip it first
-    if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
-        game.inventory[current].unequip(&mut game.messages);
-    }
-
-    if equipment.equipped { game.inventory[inventory_id].unequip(&mut game.messages); }
-    else { game.inventory[inventory_id].equip(&mut game.messages); }
-
-    UseResult::UsedAndKept
-}
-
-pub fn get_equipped_in_slot(slot: Slot, inventory: &[Object]) -> Option<usize> {
-    for (inventory_id, item) in inventory.iter().enumerate() {
-        if item.equipment.as_ref().map_or(false, |e| e.equipped && e.slot == slot) {
-            return Some(inventory_id);
-        }
-    }
-    None
-}
-
-impl std::fmt::Display for Slot {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match *self {
-            Slot::LeftHand => write!(f, "left hand"),
-            Slot::RightHand => write!(f, "right hand"),
-            Slot::Head => write!(f, "head"),
-        }
-    }
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+/// An object that can be equipped, yielding bonuses
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub max_hp_bonus: i32,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    pub evasion_bonus: i32,
+    #[serde(default)]
+    pub damage: Option<Dice>,   // Rolled in melee instead of bare hands, for weapons
+    #[serde(default)]
+    pub two_handed: bool,       // Takes up the off hand as well as the main hand
+} 
+
+/// Where on the body something is worn. Every slot holds one item, except that two rings fit
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Slot {
+    Head,
+    Body,
+    Hands,
+    Feet,
+    Ring,
+    Amulet,
+    MainHand,
+    OffHand,
+}
+
+impl Slot {
+    /// Every slot, in the order the equipment screen shows them
+    pub const ALL: [Slot; 8] = [
+        Slot::Head, Slot::Body, Slot::Hands, Slot::Feet, Slot::Ring, Slot::Amulet, Slot::MainHand, Slot::OffHand,
+    ];
+
+    /// Where the items in this slot go, one place for each item it holds
+    fn places(self) -> &'static [&'static str] {
+        match self {
+            Slot::Head => &["Head"],
+            Slot::Body => &["Body"],
+            Slot::Hands => &["Hands"],
+            Slot::Feet => &["Feet"],
+            Slot::Ring => &["Left ring", "Right ring"],
+            Slot::Amulet => &["Amulet"],
+            Slot::MainHand => &["Main hand"],
+            Slot::OffHand => &["Off hand"],
+        }
+    }
+}
+
+/// Equip an item and show a message
+pub fn equip(item_id: ObjectId, objects: &mut Objects, messages: &mut Messages) {
+    if !objects.items.contains(item_id) {
+        messages.add(format!("Cannot equip {} because it's not an Item.", objects.names[item_id]), RED);
+        return;
+    }
+
+    if let Some(equipment) = objects.equipment.get_mut(item_id) {
+        if !equipment.equipped {
+            equipment.equipped = true;
+            let slot = equipment.slot;
+            messages.add(format!("Equipped {} on {}.", objects.display_name(item_id), slot), LIGHT_GREEN);
+        }
+    } else {
+        messages.add(format!("Cannot equip {} because it's not Equipment.", objects.names[item_id]), RED);
+    }
+}
+
+pub fn unequip(item_id: ObjectId, objects: &mut Objects, messages: &mut Messages) {
+    if !objects.items.contains(item_id) {
+        messages.add(format!("Cannot unequip {} because it's not an Item.", objects.names[item_id]), RED);
+        return;
+    }
+
+    if let Some(equipment) = objects.equipment.get_mut(item_id) {
+        if equipment.equipped {
+            equipment.equipped = false;
+            let slot = equipment.slot;
+            messages.add(format!("Unequiped {} from {}.", objects.display_name(item_id), slot), LIGHT_YELLOW);
+        }
+    } else {
+        messages.add(format!("Cannot unequip {} because it's not Equipment", objects.names[item_id]), RED);
+    }
+}
+
+impl Objects {
+    /// Returns a list of everything `id` has equipped. Only objects with an inventory can equip anything
+    pub fn get_all_equipped(&self, id: ObjectId) -> Vec<Equipment> {
+        self.inventories
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter_map(|&item_id| self.equipment.get(item_id))
+            .filter(|e| e.equipped)
+            .copied()
+            .collect()
+    }
+}
+
+
+//-----------------------------//
+// EQUIPMENT BONUSES           //
+//-----------------------------//
+impl Objects {
+    pub fn max_hp(&self, id: ObjectId) -> i32 {
+        let base_max_hp = self.fighters.get(id).map_or(0, |f| f.base_max_hp);
+        let bonus: i32 = self.get_all_equipped(id).iter().map(|e| e.max_hp_bonus).sum();
+        base_max_hp + bonus
+    }
+
+    pub fn power(&self, id: ObjectId) -> i32 {
+        let base_power = self.fighters.get(id).map_or(0, |f| f.base_power);
+        let bonus: i32 = self.get_all_equipped(id).iter().map(|e| e.power_bonus).sum();
+        base_power + bonus
+    }
+
+    pub fn defense(&self, id: ObjectId) -> i32 {
+        let base_defense = self.fighters.get(id).map_or(0, |f| f.base_defense);
+        let bonus: i32 = self
+            .get_all_equipped(id)
+            .iter()
+            .map(|e| e.defense_bonus)
+            .sum();
+        base_defense + bonus
+    }
+
+    pub fn evasion(&self, id: ObjectId) -> i32 {
+        let base_evasion = self.fighters.get(id).map_or(0, |f| f.base_evasion);
+        let bonus: i32 = self.get_all_equipped(id).iter().map(|e| e.evasion_bonus).sum();
+        base_evasion + bonus
+    }
+
+    /// The damage dice of the best weapon `id` wields, or its bare hands
+    pub fn melee_damage(&self, id: ObjectId) -> Dice {
+        self.get_all_equipped(id)
+            .iter()
+            .filter_map(|e| e.damage)
+            .max_by_key(|dice| dice.average())
+            .unwrap_or(UNARMED_DAMAGE)
+    }
+}
+
+
+pub fn toggle_equipment(item_id: ObjectId, _frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    let equipment = match objects.equipment.get(item_id) {
+        Some(&equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+
+    if equipment.equipped {
+        unequip(item_id, objects, &mut game.messages);
+    } else {
+        // Whatever is in the way comes off first
+        for current in in_the_way(item_id, PLAYER, objects) {
+            unequip(current, objects, &mut game.messages);
+        }
+        equip(item_id, objects, &mut game.messages);
+    }
+
+    UseResult::UsedAndKept
+}
+
+/// The items `owner` has equipped in `slot`, oldest first. A two-handed weapon fills the off hand too
+pub fn get_equipped_in_slot(slot: Slot, owner: ObjectId, objects: &Objects) -> Vec<ObjectId> {
+    objects.inventories[owner]
+        .iter()
+        .copied()
+        .filter(|&item_id| {
+            objects.equipment.get(item_id).is_some_and(|e| {
+                e.equipped && (e.slot == slot || (slot == Slot::OffHand && e.slot == Slot::MainHand && e.two_handed))
+            })
+        })
+        .collect()
+}
+
+/// What `owner` would have to take off to equip the item: whatever fills its slots, or the
+/// oldest ring when both are taken
+fn in_the_way(item_id: ObjectId, owner: ObjectId, objects: &Objects) -> Vec<ObjectId> {
+    let Some(&equipment) = objects.equipment.get(item_id) else {
+        return vec![];
+    };
+    let slots = if equipment.two_handed { vec![equipment.slot, Slot::OffHand] } else { vec![equipment.slot] };
+
+    let mut in_the_way = vec![];
+    for slot in slots {
+        let equipped: Vec<ObjectId> = get_equipped_in_slot(slot, owner, objects).into_iter().filter(|&id| id != item_id).collect();
+        if equipped.len() >= slot.places().len() && !in_the_way.contains(&equipped[0]) {
+            in_the_way.push(equipped[0]);
+        }
+    }
+    in_the_way
+}
+
+/// Whether `owner` could equip the item without taking anything else off
+pub fn fits_free_slot(item_id: ObjectId, owner: ObjectId, objects: &Objects) -> bool {
+    objects.equipment.contains(item_id) && in_the_way(item_id, owner, objects).is_empty()
+}
+
+/// What `owner` wears, one line for each place on the body
+pub fn paper_doll(owner: ObjectId, objects: &Objects) -> String {
+    let mut lines = vec![];
+    for slot in Slot::ALL {
+        let equipped = get_equipped_in_slot(slot, owner, objects);
+        for (index, place) in slot.places().iter().enumerate() {
+            let worn = match equipped.get(index) {
+                Some(&item_id) if objects.equipment[item_id].slot != slot => format!("({}, two-handed)", objects.display_name(item_id)),
+                Some(&item_id) => objects.display_name(item_id),
+                None => "-".into(),
+            };
+            lines.push(format!("{:<12}{}", format!("{}:", place), worn));
+        }
+    }
+    lines.join("\n")
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Slot::Head => write!(f, "head"),
+            Slot::Body => write!(f, "body"),
+            Slot::Hands => write!(f, "hands"),
+            Slot::Feet => write!(f, "feet"),
+            Slot::Ring => write!(f, "finger"),
+            Slot::Amulet => write!(f, "neck"),
+            Slot::MainHand => write!(f, "main hand"),
+            Slot::OffHand => write!(f, "off hand"),
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+/// Field of view computed over the game map, independent of any frontend.
+/// Uses circular ray casting, the same approach as libtcod's `FovAlgorithm::Basic`
+#[derive(Clone, Debug, Default)]
+pub struct FovMap {
+    width: i32,
+    height: i32,
+    transparent: Vec<bool>,
+    in_fov: Vec<bool>,
+}
+
+impl FovMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        let size = (width * height) as usize;
+        FovMap {
+            width,
+            height,
+            transparent: vec![false; size],
+            in_fov: vec![false; size],
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        } else {
+            Some((y * self.width + x) as usize)
+        }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, transparent: bool) {
+        if let Some(index) = self.index(x, y) {
+            self.transparent[index] = transparent;
+        }
+    }
+
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|index| self.in_fov[index])
+    }
+
+    /// Recompute which tiles are visible from (x, y). A radius of 0 means unlimited
+    pub fn compute_fov(&mut self, x: i32, y: i32, radius: i32, light_walls: bool) {
+        self.in_fov.iter_mut().for_each(|tile| *tile = false);
+        let origin = match self.index(x, y) {
+            Some(index) => index,
+            None => return,
+        };
+        self.in_fov[origin] = true;
+
+        let (x_min, x_max, y_min, y_max) = if radius > 0 {
+            (x - radius, x + radius, y - radius, y + radius)
+        } else {
+            (0, self.width - 1, 0, self.height - 1)
+        };
+        let radius_squared = radius * radius;
+
+        // Cast a ray to every tile on the edge of the bounding square
+        for edge_x in x_min..=x_max {
+            self.cast_ray(x, y, edge_x, y_min, radius_squared, light_walls);
+            self.cast_ray(x, y, edge_x, y_max, radius_squared, light_walls);
+        }
+        for edge_y in (y_min + 1)..y_max {
+            self.cast_ray(x, y, x_min, edge_y, radius_squared, light_walls);
+            self.cast_ray(x, y, x_max, edge_y, radius_squared, light_walls);
+        }
+    }
+
+    fn cast_ray(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, radius_squared: i32, light_walls: bool) {
+        for (x, y) in line(x0, y0, x1, y1).skip(1) {
+            let index = match self.index(x, y) {
+                Some(index) => index,
+                None => return,
+            };
+            if radius_squared > 0 && (x - x0).pow(2) + (y - y0).pow(2) > radius_squared {
+                return;
+            }
+
+            if self.transparent[index] {
+                self.in_fov[index] = true;
+            } else {
+                if light_walls { self.in_fov[index] = true; }
+                return;
+            }
+        }
+    }
+}
+
+
+/// Iterate over the tiles on a Bresenham line from (x0, y0) to (x1, y1), both ends included
+pub fn line(x0: i32, y0: i32, x1: i32, y1: i32) -> impl Iterator<Item = (i32, i32)> {
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let step_x = if x0 < x1 { 1 } else { -1 };
+    let step_y = if y0 < y1 { 1 } else { -1 };
+
+    let mut error = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+    let mut done = false;
+
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let current = (x, y);
+        if x == x1 && y == y1 {
+            done = true;
+        } else {
+            let double_error = 2 * error;
+            if double_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if double_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        Some(current)
+    })
+}
+
+/// Whether nothing blocks sight between two tiles. The tiles at either end may be opaque themselves
+pub fn has_line_of_sight(from: (i32, i32), to: (i32, i32), map: &crate::Map) -> bool {
+    line(from.0, from.1, to.0, to.1)
+        .filter(|&tile| tile != from && tile != to)
+        .all(|(x, y)| !map[x as usize][y as usize].block_sight)
+}
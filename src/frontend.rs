@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+use crate::*;
+
+/// A command issued by the player, independent of how it was entered
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Command {
+    Move { dx: i32, dy: i32 },
+    Wait,
+    PickUp,
+    UseItem,
+    DropItem,
+    Fire,
+    Throw,
+    TakeStairs,
+    CharacterInfo,
+    EquipmentScreen,
+    CombatLog,
+    Exit,
+}
+
+/// Every answer a frontend can give to the game core, in the order it was asked for
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Input {
+    Command(Command),
+    Inventory(Option<usize>),
+    Target(Option<(i32, i32)>),
+    Quantity(Option<u32>),
+    LevelUp(usize),
+}
+
+/// Draws the game state. The core never draws anything itself
+pub trait Renderer {
+    fn render(&mut self, game: &Game, objects: &Objects);
+    fn msgbox(&mut self, text: &str, width: i32);
+}
+
+/// Supplies the player's decisions whenever the core needs one
+pub trait InputSource {
+    /// `true` once the frontend can no longer supply input (window closed, script finished)
+    fn is_closed(&self) -> bool;
+
+    /// Poll for the next command. `None` means nothing happened this frame
+    fn next_command(&mut self, game: &Game, objects: &Objects) -> Option<Command>;
+
+    /// Pick an entry in the player's inventory, or `None` to cancel
+    fn choose_inventory_item(&mut self, objects: &Objects, header: &str) -> Option<usize>;
+
+    /// Pick a target tile, or `None` to cancel. Use `is_valid_target` to check a choice
+    fn choose_target_tile(&mut self, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<(i32, i32)>;
+
+    /// Pick how many of something, up to `max`, or `None` to cancel
+    fn choose_quantity(&mut self, header: &str, max: u32) -> Option<u32>;
+
+    /// Pick one of the level up options. Cannot be cancelled
+    fn choose_level_up(&mut self, options: &[String]) -> usize;
+}
+
+/// Everything the turn loop needs from the outside world
+pub trait Frontend: Renderer + InputSource {}
+
+impl<T: Renderer + InputSource> Frontend for T {}
+
+
+/// Frontend that draws nothing and feeds the game a fixed list of inputs.
+/// Used to drive whole games without a window, e.g. on CI
+pub struct ScriptedFrontend {
+    inputs: VecDeque<Input>,
+    error: Option<String>,  // Why the script was given up on, if it didn't fit the game
+}
+
+impl ScriptedFrontend {
+    pub fn new(inputs: Vec<Input>) -> Self {
+        ScriptedFrontend { inputs: inputs.into(), error: None }
+    }
+
+    /// What went wrong, if the script stopped fitting the game before it ran out
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Give up on the rest of the script. Closes the frontend, so the game ends
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+        self.inputs.clear();
+    }
+}
+
+impl Renderer for ScriptedFrontend {
+    fn render(&mut self, _game: &Game, _objects: &Objects) {}
+
+    fn msgbox(&mut self, _text: &str, _width: i32) {}
+}
+
+impl InputSource for ScriptedFrontend {
+    fn is_closed(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    fn next_command(&mut self, _game: &Game, _objects: &Objects) -> Option<Command> {
+        match self.inputs.pop_front() {
+            Some(Input::Command(command)) => Some(command),
+            Some(other) => {
+                self.fail(format!("Script expected a command, found {:?}", other));
+                None
+            }
+            None => Some(Command::Exit),
+        }
+    }
+
+    fn choose_inventory_item(&mut self, objects: &Objects, _header: &str) -> Option<usize> {
+        match self.inputs.pop_front() {
+            Some(Input::Inventory(Some(index))) if index >= objects.inventories[PLAYER].len() => {
+                self.fail(format!("Script chose inventory item {}, but there are only {}", index, objects.inventories[PLAYER].len()));
+                None
+            }
+            Some(Input::Inventory(choice)) => choice,
+            Some(other) => {
+                self.fail(format!("Script expected an inventory choice, found {:?}", other));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn choose_target_tile(&mut self, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<(i32, i32)> {
+        match self.inputs.pop_front() {
+            Some(Input::Target(Some((x, y)))) if !is_valid_target(x, y, game, objects, max_range) => {
+                self.fail(format!("Script chose ({}, {}), which can't be targeted", x, y));
+                None
+            }
+            Some(Input::Target(choice)) => choice,
+            Some(other) => {
+                self.fail(format!("Script expected a target, found {:?}", other));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn choose_quantity(&mut self, _header: &str, max: u32) -> Option<u32> {
+        match self.inputs.pop_front() {
+            Some(Input::Quantity(Some(quantity))) if quantity > max => {
+                self.fail(format!("Script chose {} of something, but there are only {}", quantity, max));
+                None
+            }
+            Some(Input::Quantity(choice)) => choice,
+            Some(other) => {
+                self.fail(format!("Script expected a quantity, found {:?}", other));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn choose_level_up(&mut self, options: &[String]) -> usize {
+        match self.inputs.pop_front() {
+            Some(Input::LevelUp(choice)) if choice < options.len() => choice,
+            Some(other) => {
+                self.fail(format!("Script expected one of {} level up choices, found {:?}", options.len(), other));
+                0
+            }
+            None => 0,
+        }
+    }
+}
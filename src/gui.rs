@@ -1,199 +1,715 @@
-use tcod::colors::*;
-
-use crate::*;
-
-pub fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recompute: bool) {
-    if fov_recompute {
-        // recompute FOV if needed (the player moved or something)
-        let player = &objects[PLAYER];
-        tcod.fov
-            .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGORITHM);
-    }
-
-    // go through all tiles, and set their background colour
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let visible = tcod.fov.is_in_fov(x, y);
-            let wall = game.map[x as usize][y as usize].block_sight;
-            let colour = match (visible, wall) {
-                // outside of field of view:
-                (false, true) => COLOUR_DARK_WALL,
-                (false, false) => COLOUR_DARK_GROUND,
-                // inside fov:
-                (true, true) => COLOUR_LIGHT_WALL,
-                (true, false) => COLOUR_LIGHT_GROUND,
-            };
-
-            let explored = &mut game.map[x as usize][y as usize].explored;
-            if visible {
-                // since it's visible, explore it
-                *explored = true;
-            }
-            if *explored {
-                // show explored tiles only (any visible tile is explored already)
-                tcod.con
-                    .set_char_background(x, y, colour.into(), BackgroundFlag::Set);
-            }
-        }
-    }
-
-    let mut to_draw: Vec<_> = objects
-        .iter()
-        .filter(|o| tcod.fov.is_in_fov(o.x, o.y) 
-            || (o.always_visible && game.map[o.x as usize][o.y as usize].explored))
-        .collect();
-    // sort so that non-blocking objects come first
-    to_draw.sort_by(|o1, o2| o1.blocks.cmp(&o2.blocks));
-    // draw the objects in the list
-    for object in &to_draw {
-        object.draw(&mut tcod.con);
-    }
-
-    // blit the contents of "con" to the root console
-    blit(
-        &tcod.con,
-        (0, 0),
-        (MAP_WIDTH, MAP_HEIGHT),
-        &mut tcod.root,
-        (0, 0),
-        1.0,
-        1.0,
-    );
-
-    // prepare to render the GUI panel
-    tcod.panel.set_default_background(BLACK);
-    tcod.panel.clear();
-
-    // print the game messages, one line at a time
-    let mut y = MSG_HEIGHT as i32;
-    for &(ref msg, color) in game.messages.iter().rev() {
-        let msg_height = tcod.panel.get_height_rect(MSG_X, y, MSG_WIDTH, 0, msg);
-        y -= msg_height;
-        if y < 0 {
-            break;
-        }
-        tcod.panel.set_default_foreground(color.into());
-        tcod.panel.print_rect(MSG_X, y, MSG_WIDTH, 0, msg);
-    }
-
-    // Show the player's stats
-    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
-    let max_hp = objects[PLAYER].max_hp(game);    
-    render_bar(
-        &mut tcod.panel,
-        1,
-        1,
-        BAR_WIDTH,
-        "HP",
-        hp,
-        max_hp,
-        LIGHT_RED.into(),
-        DARKER_RED.into(),
-    );
-
-    // Show player's dungeon level
-    tcod.panel.print_ex(1, 3, BackgroundFlag::None, TextAlignment::Left, format!("Dungeon level: {}", game.dungeon_level));
-
-    // display names of objects under the mouse
-    tcod.panel.set_default_foreground(LIGHT_GREY);
-    tcod.panel.print_ex(
-        1,
-        0,
-        BackgroundFlag::None,
-        TextAlignment::Left,
-        get_names_under_mouse(tcod.mouse, objects, &tcod.fov),
-    );
-
-    // blit the contents of `panel` to the root console
-    blit(
-        &tcod.panel,
-        (0, 0),
-        (SCREEN_WIDTH, PANEL_HEIGHT),
-        &mut tcod.root,
-        (0, PANEL_Y),
-        1.0,
-        1.0,
-    );
-}
-
-pub fn render_bar(panel: &mut Offscreen, x: i32, y: i32, total_width: i32, name: &str, value: i32, maximum: i32, bar_colour: SerializableColour, back_colour: SerializableColour) {
-    // Calculate width of bar
-    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
-
-    // Render background
-    panel.set_default_background(back_colour.into());
-    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
-
-    // Render bar on top
-    panel.set_default_background(bar_colour.into());
-    if bar_width > 0 {
-        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
-    };
-
-    // Add centered text w/ values
-    panel.set_default_foreground(WHITE);
-    panel.print_ex(x + total_width / 2, y, BackgroundFlag::None, TextAlignment::Center, &format!("{}: {}/{}", name, value, maximum));
-}
-
-
-pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root) -> Option<usize> {
-    assert!(options.len() <= 26, "Cannot have a menu with more than 26 options");
-
-    // Calculate total height for header (after wrap) and one line per option
-    let header_height = if header.is_empty() {
-        0
-    } else {
-        root.get_height_rect(0, 0, width, SCREEN_HEIGHT, header)
-    };
-    let height = options.len() as i32 + header_height;
-
-    // Create off-screen console for menu window
-    let mut window = Offscreen::new(width, height);
-
-    // Print header with wrap
-    window.set_default_foreground(WHITE);
-    window.print_rect_ex(0, 0, width, height, BackgroundFlag::None, TextAlignment::Left, header);
-
-    // Print all options
-    for (index, option_text) in options.iter().enumerate() {
-        let menu_letter = (b'a' + index as u8) as char;
-        let text = format!("({}) {}", menu_letter, option_text.as_ref());
-        window.print_ex(0, header_height + index as i32, BackgroundFlag::None, TextAlignment::Left, text);
-    }
-
-    // Blit contents of window to root
-    let x = SCREEN_WIDTH / 2 - width / 2;
-    let y = SCREEN_HEIGHT / 2 - height / 2;
-    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
-
-    // Present root console to the player and wait for key-press
-    root.flush();
-    let key = root.wait_for_keypress(true);
-
-    // Convert ASCII code to index
-    // Return option if index is valid
-    if key.printable.is_alphabetic() {
-        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
-
-        if index < options.len() { Some(index) } else { None }
-    } else { None }
-}
-
-
-pub fn inventory_menu(inventory: &[Object], header: &str, root: &mut Root) -> Option<usize> {
-    let options = if inventory.len() == 0 { 
-        vec!["Inventory is empty.".into()] 
-    } else { 
-        inventory.iter().map(|item| {
-            match item.equipment {
-                Some(equipment) if equipment.equipped => {
-                    format!("{} (on {})", item.name, equipment.slot)
-                }
-                _ => item.name.clone(),
-            }
-        }).collect()
-    };
-
-    let inventory_index = menu(header, &options, INVENTORY_WIDTH, root);
-    if inventory.len() > 0 { inventory_index } else { None }
-}
\ No newline at end of file
+use tcod::{
+    colors::Color,
+    input::{self, Event},
+};
+
+use crate::*;
+
+impl From<Color> for SerializableColour {
+    fn from(color: Color) -> Self {
+        SerializableColour { r: color.r, g: color.g, b: color.b }
+    }
+}
+
+impl From<SerializableColour> for Color {
+    fn from(scolor: SerializableColour) -> Self {
+        Color::new(scolor.r, scolor.g, scolor.b)
+    }
+}
+
+impl Renderable {
+    pub fn draw(&self, x: i32, y: i32, con: &mut dyn Console) {
+        con.set_default_foreground(self.colour.into());
+        con.put_char(x, y, self.char, BackgroundFlag::None);
+    }
+}
+
+
+//-----------------------------//
+// TCOD FRONTEND               //
+//-----------------------------//
+impl Renderer for Tcod {
+    fn render(&mut self, game: &Game, objects: &Objects) {
+        render_all(self, game, objects);
+        self.root.flush();
+    }
+
+    fn msgbox(&mut self, text: &str, width: i32) {
+        msgbox(text, width, &mut self.root);
+    }
+}
+
+impl InputSource for Tcod {
+    fn is_closed(&self) -> bool {
+        self.root.window_closed()
+    }
+
+    fn next_command(&mut self, _game: &Game, _objects: &Objects) -> Option<Command> {
+        use tcod::input::KeyCode::*;
+
+        // Check for mouse
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, Event::Mouse(m))) => self.mouse = m,
+            Some((_, Event::Key(k))) => self.key = k,
+            _ => self.key = Default::default(),
+        }
+
+        let command = match (self.key, self.key.text()) {
+            (Key { code: Enter, alt: true, .. }, _) => {
+                // Alt+Enter: toggle fullscreen
+                let fullscreen = self.root.is_fullscreen();
+                self.root.set_fullscreen(!fullscreen);
+                return None;
+            }
+            (Key { code: Escape, .. }, _) => Command::Exit,    // Exit game
+
+            // Movement keys
+            (Key { code: Up, .. }, _) | (Key { code: NumPad8, .. }, _) => Command::Move { dx: 0, dy: -1 },
+            (Key { code: Down, .. }, _) | (Key { code: NumPad2, .. }, _) => Command::Move { dx: 0, dy: 1 },
+            (Key { code: Left, .. }, _) | (Key { code: NumPad4, .. }, _) => Command::Move { dx: -1, dy: 0 },
+            (Key { code: Right, .. }, _) | (Key { code: NumPad6, .. }, _) => Command::Move { dx: 1, dy: 0 },
+            (Key { code: Home, .. }, _) | (Key { code: NumPad7, .. }, _) => Command::Move { dx: -1, dy: -1 },
+            (Key { code: PageUp, .. }, _) | (Key { code: NumPad9, .. }, _) => Command::Move { dx: 1, dy: -1 },
+            (Key { code: End, .. }, _) | (Key { code: NumPad1, .. }, _) => Command::Move { dx: -1, dy: 1 },
+            (Key { code: PageDown, .. }, _) | (Key { code: NumPad3, .. }, _) => Command::Move { dx: 1, dy: 1 },
+            (Key { code: NumPad5, .. }, _) => Command::Wait,
+
+            (Key { code: Text, .. }, "e") => Command::PickUp,
+            (Key { code: Text, .. }, "i") => Command::UseItem,
+            (Key { code: Text, .. }, "d") => Command::DropItem,
+            (Key { code: Text, .. }, "f") => Command::Fire,
+            (Key { code: Text, .. }, "t") => Command::Throw,
+            (Key { code: Enter, .. }, _) => Command::TakeStairs,
+            (Key { code: Text, .. }, "c") => Command::CharacterInfo,
+            (Key { code: Text, .. }, "w") => Command::EquipmentScreen,
+            (Key { code: Text, .. }, "l") => Command::CombatLog,
+
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    fn choose_inventory_item(&mut self, objects: &Objects, header: &str) -> Option<usize> {
+        inventory_menu(objects, header, &mut self.root)
+    }
+
+    fn choose_target_tile(&mut self, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<(i32, i32)> {
+        use tcod::input::KeyCode::Escape;
+
+        loop {
+            // Render the screen
+            // This erases the inventory and shows the name of objects under the mouse
+            self.root.flush();
+            let event = input::check_for_event(input::KEY_PRESS | input::MOUSE).map(|e| e.1);
+
+            match event {
+                Some(Event::Mouse(m)) => self.mouse = m,
+                Some(Event::Key(k)) => self.key = k,
+                None => self.key = Default::default(),
+            }
+            render_all(self, game, objects);
+
+            let (x, y) = (self.mouse.cx as i32, self.mouse.cy as i32);
+
+            // Accept the target if the player clicked in FOV and in range
+            if self.mouse.lbutton_pressed && is_valid_target(x, y, game, objects, max_range) { return Some((x, y)); }
+            if self.mouse.rbutton_pressed || self.key.code == Escape { return None; }   // Cancelled
+        }
+    }
+
+    fn choose_quantity(&mut self, header: &str, max: u32) -> Option<u32> {
+        // Anything more than there is means all of them
+        let quantity = number_input(header, QUANTITY_WIDTH, &mut self.root)?;
+        Some(quantity.min(max as u64) as u32)
+    }
+
+    fn choose_level_up(&mut self, options: &[String]) -> usize {
+        loop {
+            // Keep asking until a choice is made
+            if let Some(choice) = menu("Level up! Choose a stat to raise:\n", options, LEVEL_SCREEN_WIDTH, &mut self.root) {
+                return choice;
+            }
+        }
+    }
+}
+
+
+//-----------------------------//
+// REPLAY FRONTEND             //
+//-----------------------------//
+const REPLAY_DEFAULT_DELAY: u32 = 4;    // Frames between replayed commands
+const REPLAY_MAX_DELAY: u32 = 32;
+const REPLAY_END_WIDTH: i32 = 50;
+
+/// Draws with tcod but takes its inputs from a recorded session.
+/// Space pauses, +/- change the speed and Escape stops the replay
+pub struct ReplayFrontend<'a> {
+    tcod: &'a mut Tcod,
+    script: ScriptedFrontend,
+    delay: u32,
+    frames_waited: u32,
+    paused: bool,
+    stopped: bool,
+}
+
+impl<'a> ReplayFrontend<'a> {
+    pub fn new(tcod: &'a mut Tcod, inputs: Vec<Input>) -> Self {
+        ReplayFrontend {
+            tcod,
+            script: ScriptedFrontend::new(inputs),
+            delay: REPLAY_DEFAULT_DELAY,
+            frames_waited: 0,
+            paused: false,
+            stopped: false,
+        }
+    }
+
+    fn handle_controls(&mut self) {
+        use tcod::input::KeyCode::{Escape, Spacebar, Text, NumPadAdd, NumPadSubtract};
+
+        match input::check_for_event(input::KEY_PRESS) {
+            Some((_, Event::Key(k))) => self.tcod.key = k,
+            _ => self.tcod.key = Default::default(),
+        }
+
+        match (self.tcod.key.code, self.tcod.key.text()) {
+            (Escape, _) => self.stopped = true,
+            (Spacebar, _) | (Text, " ") => self.paused = !self.paused,
+            (NumPadAdd, _) | (Text, "+") => self.delay /= 2,
+            (NumPadSubtract, _) | (Text, "-") => self.delay = (self.delay * 2).clamp(1, REPLAY_MAX_DELAY),
+            _ => {}
+        }
+    }
+}
+
+impl Renderer for ReplayFrontend<'_> {
+    fn render(&mut self, game: &Game, objects: &Objects) {
+        render_all(self.tcod, game, objects);
+
+        let status = if self.paused {
+            "REPLAY (paused)".to_string()
+        } else {
+            format!("REPLAY ({} frames/turn)", self.delay)
+        };
+        self.tcod.root.set_default_foreground(LIGHT_YELLOW.into());
+        self.tcod.root.print_ex(SCREEN_WIDTH - 1, 0, BackgroundFlag::None, TextAlignment::Right, status);
+        self.tcod.root.flush();
+    }
+
+    fn msgbox(&mut self, _text: &str, _width: i32) {
+        // Don't wait for a key press in the middle of a replay
+    }
+}
+
+impl InputSource for ReplayFrontend<'_> {
+    fn is_closed(&self) -> bool {
+        self.stopped || self.script.is_closed() || self.tcod.root.window_closed()
+    }
+
+    fn next_command(&mut self, game: &Game, objects: &Objects) -> Option<Command> {
+        self.handle_controls();
+        if self.paused || self.stopped || self.frames_waited < self.delay {
+            self.frames_waited += 1;
+            return None;
+        }
+
+        self.frames_waited = 0;
+        self.script.next_command(game, objects)
+    }
+
+    fn choose_inventory_item(&mut self, objects: &Objects, header: &str) -> Option<usize> {
+        self.script.choose_inventory_item(objects, header)
+    }
+
+    fn choose_target_tile(&mut self, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<(i32, i32)> {
+        self.script.choose_target_tile(game, objects, max_range)
+    }
+
+    fn choose_quantity(&mut self, header: &str, max: u32) -> Option<u32> {
+        self.script.choose_quantity(header, max)
+    }
+
+    fn choose_level_up(&mut self, options: &[String]) -> usize {
+        self.script.choose_level_up(options)
+    }
+}
+
+/// Rebuild a recorded session and play it back in the window
+pub fn watch_replay(tcod: &mut Tcod, path: &str) {
+    let replay = Replay::load(path);
+    match replay.and_then(|replay| Ok((replay.start.restore()?, replay.inputs))) {
+        Ok(((mut game, mut objects), inputs)) => {
+            let mut frontend = ReplayFrontend::new(tcod, inputs);
+            play_game(&mut frontend, &mut game, &mut objects);
+            // A replay that no longer fits the game stops where it went wrong
+            let ending = match frontend.script.error() {
+                Some(error) => format!("\nThe replay went out of step with the game:\n{}\n", error),
+                None => "\nEnd of replay.\n".to_string(),
+            };
+            msgbox(&ending, REPLAY_END_WIDTH, &mut tcod.root);
+        }
+        Err(_e) => {
+            msgbox("\nNo replay to watch :(\n", 24, &mut tcod.root);
+        }
+    }
+}
+
+
+pub fn main_menu(tcod: &mut Tcod) {
+    let img = tcod::image::Image::from_file("assets/main_menu.png")
+        .expect("Menu image not found");
+    
+
+    while !tcod.root.window_closed() {
+        // Show background image
+        tcod::image::blit_2x(&img, (0, 0), (-1, -1), &mut tcod.root, (0, 0));
+
+        // Title and credits
+        tcod.root.set_default_foreground(LIGHT_YELLOW.into());
+        tcod.root.print_ex(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2 - 4, BackgroundFlag::None, TextAlignment::Center, "TOMBS OF THE FALLEN");
+        tcod.root.print_ex(SCREEN_WIDTH / 2, SCREEN_HEIGHT - 2, BackgroundFlag::None, TextAlignment::Center, "By Caerwyn S-R");
+
+        // Show options and wait for choice
+        let choices = &["New Game", "New Game with seed", "Load Game", "Watch Replay", "Quit"];
+        let choice = menu("", choices, 24, &mut tcod.root);
+
+        match choice {
+            Some(0) | Some(1) => {
+                // Either roll a seed, or let the player enter one to replay a run
+                let seed = if choice == Some(0) {
+                    Some(random_seed())
+                } else {
+                    number_input("\nEnter a seed, then press Enter:", 36, &mut tcod.root)
+                };
+
+                if let Some(seed) = seed
+                    && let Some(slot) = new_slot_name(tcod)
+                    && let Some(format) = save_format_choice(tcod)
+                {
+                    // Proceed with new game
+                    let (mut game, mut objects) = new_game(seed);
+                    play_recorded(tcod, ReplayStart::NewGame { seed }, &mut game, &mut objects);
+                    save_and_report(tcod, &slot, &game, &objects, format);
+                }
+            }
+            Some(2) => {
+                // Pick a save slot, then load, rename or delete it
+                if let Some(slot) = slot_picker(tcod) {
+                    slot_menu(tcod, &slot);
+                }
+            }
+            Some(3) => {
+                // Watch the last recorded session
+                watch_replay(tcod, REPLAY_FILE);
+            }
+            Some(4) => {
+                // Quit
+                break;
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// Ask for the name of the slot a new game will be saved in.
+/// Overwriting an existing slot needs confirming
+fn new_slot_name(tcod: &mut Tcod) -> Option<String> {
+    let slot = text_input("\nName your save, then press Enter:", MAX_SLOT_NAME_LENGTH, is_valid_slot_char, 36, &mut tcod.root)?;
+    if slot_exists(&slot) {
+        let confirm_choices = &["Yes", "No"];
+        let header = format!("\nA save called {} already exists. Starting a new game will erase it. Are you sure?", slot);
+        if menu(&header, confirm_choices, 36, &mut tcod.root) != Some(0) {
+            return None;
+        }
+    }
+    Some(slot)
+}
+
+/// Ask how a new game should be saved
+fn save_format_choice(tcod: &mut Tcod) -> Option<SaveFormat> {
+    let choices = &["Compact (binary)", "Readable (JSON)"];
+    match menu("\nHow should this game be saved?", choices, 36, &mut tcod.root)? {
+        0 => Some(SaveFormat::Binary),
+        _ => Some(SaveFormat::Json),
+    }
+}
+
+/// Show every save slot with a summary of its game, and return the chosen one
+fn slot_picker(tcod: &mut Tcod) -> Option<String> {
+    let slots = list_slots();
+    if slots.is_empty() {
+        msgbox("\nNo saved game to load :(\n", 24, &mut tcod.root);
+        return None;
+    }
+
+    // Menus are limited to one option per letter, so long lists are shown a page at a time
+    let pages = slots.len().div_ceil(SLOTS_PER_PAGE);
+    let mut page = 0;
+    loop {
+        let start = page * SLOTS_PER_PAGE;
+        let shown = &slots[start..slots.len().min(start + SLOTS_PER_PAGE)];
+        let mut options: Vec<String> = shown
+            .iter()
+            .map(|slot| match &slot.metadata {
+                Some(m) => format!(
+                    "{:<20} Lvl {:>2}  Depth {:>2}  Turn {:>5}  {}",
+                    slot.name, m.character_level, m.dungeon_level, m.turns, format_timestamp(m.timestamp)
+                ),
+                None => format!("{:<20} (no details)", slot.name),
+            })
+            .collect();
+
+        let next_page = (page + 1 < pages).then(|| {
+            options.push("Next page".into());
+            options.len() - 1
+        });
+        let previous_page = (page > 0).then(|| {
+            options.push("Previous page".into());
+            options.len() - 1
+        });
+        let header = if pages > 1 {
+            format!("Choose a saved game (page {} of {}):\n", page + 1, pages)
+        } else {
+            "Choose a saved game:\n".to_string()
+        };
+
+        match menu(&header, &options, SAVE_SLOT_WIDTH, &mut tcod.root)? {
+            choice if Some(choice) == next_page => page += 1,
+            choice if Some(choice) == previous_page => page -= 1,
+            choice => return Some(shown[choice].name.clone()),
+        }
+    }
+}
+
+fn slot_menu(tcod: &mut Tcod, slot: &str) {
+    let header = format!("\n{}\n", slot);
+    match menu(&header, &["Continue", "Rename", "Delete", "Back"], 24, &mut tcod.root) {
+        Some(0) => {
+            // Load game, keeping it in the format it was saved in
+            let format = slot_format(slot).unwrap_or(SaveFormat::Json);
+            match load_game(slot) {
+                Ok((mut game, mut objects)) => {
+                    match ReplayStart::snapshot(&game, &objects) {
+                        Ok(start) => play_recorded(tcod, start, &mut game, &mut objects),
+                        Err(_) => play_game(tcod, &mut game, &mut objects),
+                    }
+                    save_and_report(tcod, slot, &game, &objects, format);
+                }
+                Err(e) => {
+                    msgbox(&format!("\n{}\n", e), 36, &mut tcod.root);
+                }
+            }
+        }
+        Some(1) => {
+            let new_name = text_input("\nNew name, then press Enter:", MAX_SLOT_NAME_LENGTH, is_valid_slot_char, 36, &mut tcod.root);
+            if let Some(new_name) = new_name
+                && let Err(e) = rename_slot(slot, &new_name)
+            {
+                msgbox(&format!("\nCould not rename the save: {}\n", e), 36, &mut tcod.root);
+            }
+        }
+        Some(2) => {
+            let header = format!("\nDelete {} for good?", slot);
+            if menu(&header, &["Yes", "No"], 36, &mut tcod.root) == Some(0)
+                && let Err(e) = delete_slot(slot)
+            {
+                msgbox(&format!("\nCould not delete the save: {}\n", e), 36, &mut tcod.root);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Format seconds since the Unix epoch as a UTC date and time, e.g. "2024-05-17 21:04"
+fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let minutes_of_day = (timestamp % 86_400) / 60;
+
+    // Civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes_of_day / 60, minutes_of_day % 60)
+}
+
+/// Save on the way back to the main menu, telling the player if it failed
+fn save_and_report(tcod: &mut Tcod, slot: &str, game: &Game, objects: &Objects, format: SaveFormat) {
+    if let Err(e) = save_game(slot, game, objects, format) {
+        msgbox(&format!("\nCould not save the game: {}\n", e), 36, &mut tcod.root);
+    }
+}
+
+pub fn render_all(tcod: &mut Tcod, game: &Game, objects: &Objects) {
+    tcod.con.clear();
+
+    // go through all tiles, and set their background colour
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            let visible = game.fov.is_in_fov(x, y);
+            let wall = game.map[x as usize][y as usize].block_sight;
+            let colour = match (visible, wall) {
+                // outside of field of view:
+                (false, true) => COLOUR_DARK_WALL,
+                (false, false) => COLOUR_DARK_GROUND,
+                // inside fov:
+                (true, true) => COLOUR_LIGHT_WALL,
+                (true, false) => COLOUR_LIGHT_GROUND,
+            };
+
+            if game.map[x as usize][y as usize].explored {
+                // show explored tiles only (any visible tile is explored already)
+                tcod.con
+                    .set_char_background(x, y, colour.into(), BackgroundFlag::Set);
+            }
+        }
+    }
+
+    let mut to_draw: Vec<_> = objects
+        .positions
+        .iter()
+        .filter_map(|(id, &(x, y))| Some((id, (x, y), objects.renderables.get(id)?)))
+        .filter(|&(_, (x, y), r)| game.fov.is_in_fov(x, y) 
+            || (r.always_visible && game.map[x as usize][y as usize].explored))
+        .collect();
+    // sort so that non-blocking objects come first
+    to_draw.sort_by_key(|(id, ..)| objects.blockers.contains(*id));
+    // draw the objects in the list
+    for &(_, (x, y), renderable) in &to_draw {
+        renderable.draw(x, y, &mut tcod.con);
+    }
+
+    // blit the contents of "con" to the root console
+    blit(
+        &tcod.con,
+        (0, 0),
+        (MAP_WIDTH, MAP_HEIGHT),
+        &mut tcod.root,
+        (0, 0),
+        1.0,
+        1.0,
+    );
+
+    // prepare to render the GUI panel
+    tcod.panel.set_default_background(BLACK.into());
+    tcod.panel.clear();
+
+    // print the game messages, one line at a time
+    let mut y = MSG_HEIGHT as i32;
+    for &(ref msg, color) in game.messages.iter().rev() {
+        let msg_height = tcod.panel.get_height_rect(MSG_X, y, MSG_WIDTH, 0, msg);
+        y -= msg_height;
+        if y < 0 {
+            break;
+        }
+        tcod.panel.set_default_foreground(color.into());
+        tcod.panel.print_rect(MSG_X, y, MSG_WIDTH, 0, msg);
+    }
+
+    // Show the player's stats
+    let hp = objects.fighters.get(PLAYER).map_or(0, |f| f.hp);
+    let max_hp = objects.max_hp(PLAYER);    
+    render_bar(
+        &mut tcod.panel,
+        1,
+        1,
+        BAR_WIDTH,
+        "HP",
+        hp,
+        max_hp,
+        LIGHT_RED,
+        DARKER_RED,
+    );
+
+    // Show player's dungeon level
+    tcod.panel.print_ex(1, 3, BackgroundFlag::None, TextAlignment::Left, format!("Dungeon level: {}", game.dungeon_level));
+
+    // Show the player's status effects under it, as many as fit
+    for (y, status) in (4..PANEL_HEIGHT).zip(&objects.statuses[PLAYER]) {
+        tcod.panel.set_default_foreground(status.kind.colour().into());
+        tcod.panel.print_ex(1, y, BackgroundFlag::None, TextAlignment::Left, format!("{} ({})", status.kind, status.turns));
+    }
+
+    // display names of objects under the mouse
+    tcod.panel.set_default_foreground(LIGHT_GREY.into());
+    tcod.panel.print_ex(
+        1,
+        0,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        get_names_under_mouse(tcod.mouse, objects, &game.fov),
+    );
+
+    // blit the contents of `panel` to the root console
+    blit(
+        &tcod.panel,
+        (0, 0),
+        (SCREEN_WIDTH, PANEL_HEIGHT),
+        &mut tcod.root,
+        (0, PANEL_Y),
+        1.0,
+        1.0,
+    );
+}
+
+fn get_names_under_mouse(mouse: Mouse, objects: &Objects, fov_map: &FovMap) -> String {
+    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+
+    if !fov_map.is_in_fov(x, y) {
+        return String::new();
+    }
+
+    // Create a list with the names of all objects at mouse's coords
+    let names = objects
+        .at(x, y)
+        .map(|id| match objects.ais.get(id) {
+            Some(Ai::Asleep) => format!("{} (asleep)", objects.names[id]),
+            _ => objects.display_name(id),
+        })
+        .collect::<Vec<_>>();
+
+    names.join(", ")
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_bar(panel: &mut Offscreen, x: i32, y: i32, total_width: i32, name: &str, value: i32, maximum: i32, bar_colour: SerializableColour, back_colour: SerializableColour) {
+    // Calculate width of bar
+    let bar_width = (value as f32 / maximum as f32 * total_width as f32) as i32;
+
+    // Render background
+    panel.set_default_background(back_colour.into());
+    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
+
+    // Render bar on top
+    panel.set_default_background(bar_colour.into());
+    if bar_width > 0 {
+        panel.rect(x, y, bar_width, 1, false, BackgroundFlag::Screen);
+    };
+
+    // Add centered text w/ values
+    panel.set_default_foreground(WHITE.into());
+    panel.print_ex(x + total_width / 2, y, BackgroundFlag::None, TextAlignment::Center, format!("{}: {}/{}", name, value, maximum));
+}
+
+
+pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Root) -> Option<usize> {
+    let options: Vec<(&str, SerializableColour)> = options.iter().map(|option| (option.as_ref(), WHITE)).collect();
+    coloured_menu(header, &options, width, root)
+}
+
+/// A menu with each option in its own colour
+pub fn coloured_menu(header: &str, options: &[(&str, SerializableColour)], width: i32, root: &mut Root) -> Option<usize> {
+    assert!(options.len() <= 26, "Cannot have a menu with more than 26 options");
+
+    // Calculate total height for header (after wrap) and one line per option
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        root.get_height_rect(0, 0, width, SCREEN_HEIGHT, header)
+    };
+    let height = options.len() as i32 + header_height;
+
+    // Create off-screen console for menu window
+    let mut window = Offscreen::new(width, height);
+
+    // Print header with wrap
+    window.set_default_foreground(WHITE.into());
+    window.print_rect_ex(0, 0, width, height, BackgroundFlag::None, TextAlignment::Left, header);
+
+    // Print all options
+    for (index, &(option_text, colour)) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text);
+        window.set_default_foreground(colour.into());
+        window.print_ex(0, header_height + index as i32, BackgroundFlag::None, TextAlignment::Left, text);
+    }
+
+    // Blit contents of window to root
+    let x = SCREEN_WIDTH / 2 - width / 2;
+    let y = SCREEN_HEIGHT / 2 - height / 2;
+    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+
+    // Present root console to the player and wait for key-press
+    root.flush();
+    let key = root.wait_for_keypress(true);
+
+    // Convert ASCII code to index
+    // Return option if index is valid
+    if key.printable.is_alphabetic() {
+        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
+
+        if index < options.len() { Some(index) } else { None }
+    } else { None }
+}
+
+
+pub fn inventory_menu(objects: &Objects, header: &str, root: &mut Root) -> Option<usize> {
+    let inventory = &objects.inventories[PLAYER];
+    let options: Vec<(String, SerializableColour)> = if inventory.is_empty() { 
+        vec![("Inventory is empty.".into(), WHITE)] 
+    } else { 
+        inventory.iter().map(|&item_id| {
+            let name = match objects.equipment.get(item_id) {
+                Some(equipment) if equipment.equipped => {
+                    format!("{} (on {})", objects.display_name(item_id), equipment.slot)
+                }
+                _ => objects.display_name(item_id),
+            };
+            // Better items stand out in the colour of their rarity
+            (name, objects.enchantments.get(item_id).map_or(WHITE, |e| e.rarity.colour()))
+        }).collect()
+    };
+
+    let options: Vec<(&str, SerializableColour)> = options.iter().map(|(name, colour)| (name.as_str(), *colour)).collect();
+    let inventory_index = coloured_menu(header, &options, INVENTORY_WIDTH, root);
+    if !inventory.is_empty() { inventory_index } else { None }
+}
+
+/// Ask the player to type a number. Returns `None` if cancelled with Escape
+pub fn number_input(header: &str, width: i32, root: &mut Root) -> Option<u64> {
+    // Any 19 digit number fits in a u64
+    text_input(header, 19, |c| c.is_ascii_digit(), width, root)?.parse().ok()
+}
+
+/// Ask the player to type up to `max_length` characters accepted by `allowed`.
+/// Returns `None` if cancelled with Escape
+pub fn text_input(header: &str, max_length: usize, allowed: fn(char) -> bool, width: i32, root: &mut Root) -> Option<String> {
+    use tcod::input::KeyCode::{Backspace, Enter, Escape};
+
+    let mut text = String::new();
+    loop {
+        let prompt = format!("{}\n\n{}_", header, text);
+        let height = root.get_height_rect(0, 0, width, SCREEN_HEIGHT, &prompt);
+
+        // Draw the prompt in its own window, like `menu` does
+        let mut window = Offscreen::new(width, height);
+        window.set_default_foreground(WHITE.into());
+        window.print_rect_ex(0, 0, width, height, BackgroundFlag::None, TextAlignment::Left, &prompt);
+
+        let x = SCREEN_WIDTH / 2 - width / 2;
+        let y = SCREEN_HEIGHT / 2 - height / 2;
+        blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+        root.flush();
+
+        let key = root.wait_for_keypress(true);
+        match key.code {
+            Enter if !text.trim().is_empty() => return Some(text.trim().to_string()),
+            Escape => return None,
+            Backspace => { text.pop(); }
+            _ if allowed(key.printable) && text.len() < max_length => text.push(key.printable),
+            _ => {}
+        }
+    }
+}
+
+pub fn msgbox(text: &str, width: i32, root: &mut Root) {
+    let options: &[&str] = &[];
+    menu(text, options, width, root);
+}
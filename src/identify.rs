@@ -0,0 +1,108 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::*;
+
+/// What unidentified potions can look like. There must be at least as many as there are potions
+pub const POTION_APPEARANCES: [&str; 10] = [
+    "murky potion", "bubbling potion", "fizzy potion", "golden potion", "smoky potion",
+    "milky potion", "glowing potion", "oily potion", "crimson potion", "viscous potion",
+];
+
+/// Unidentified items all look the same on the map, or their colour would give them away
+const DISGUISED_COLOUR: SerializableColour = LIGHT_GREY;
+
+/// Scroll labels are made up of these, e.g. "scroll labelled XYZZY KANOR"
+const SCROLL_SYLLABLES: [&str; 16] = [
+    "xy", "zzy", "ka", "nor", "ab", "ra", "cad", "el", "ven", "tho", "mi", "qua", "lo", "zu", "ith", "pra",
+];
+
+/// Which potions and scrolls look like what this run, and which of them the player knows by now.
+/// Kept by item name, and saved with the game
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Identification {
+    appearances: BTreeMap<String, String>,
+    known: BTreeSet<String>,
+}
+
+impl Identification {
+    /// Shuffle the appearances of every potion and scroll for a new run
+    pub fn new(rng: &mut GameRng) -> Self {
+        let mut potions: Vec<&str> = POTION_APPEARANCES.to_vec();
+        let mut appearances = BTreeMap::new();
+        for item in &content().items {
+            let appearance = if is_potion(item.effect) {
+                potions.remove(rng.gen_range(0, potions.len())).to_string()
+            } else if is_scroll(item.effect) {
+                scroll_label(rng, &appearances)
+            } else {
+                continue;
+            };
+            appearances.insert(item.name.clone(), appearance);
+        }
+        Identification { appearances, known: BTreeSet::new() }
+    }
+
+    pub fn is_known(&self, name: &str) -> bool {
+        self.known.contains(name)
+    }
+}
+
+/// A made up label no other scroll has yet
+fn scroll_label(rng: &mut GameRng, taken: &BTreeMap<String, String>) -> String {
+    loop {
+        let label = format!("scroll labelled {} {}", scroll_word(rng), scroll_word(rng));
+        if !taken.values().any(|appearance| *appearance == label) {
+            return label;
+        }
+    }
+}
+
+fn scroll_word(rng: &mut GameRng) -> String {
+    let syllables = rng.gen_range(2, 4);
+    (0..syllables).map(|_| SCROLL_SYLLABLES[rng.gen_range(0, SCROLL_SYLLABLES.len())]).collect::<String>().to_uppercase()
+}
+
+/// Whether items with this effect are drunk, and so look like potions until identified
+pub fn is_potion(effect: Item) -> bool {
+    matches!(effect, Item::Heal | Item::Drink(_))
+}
+
+/// Whether items with this effect are read, and so look like scrolls until identified
+pub fn is_scroll(effect: Item) -> bool {
+    matches!(effect, Item::Lightning | Item::Fireball | Item::Afflict(_) | Item::Identify)
+}
+
+/// Give every item the player hasn't identified yet its appearance. Called whenever new items are made
+pub fn disguise_items(game: &Game, objects: &mut Objects) {
+    for id in objects.items.ids() {
+        let name = &objects.names[id];
+        if let Some(appearance) = game.identification.appearances.get(name)
+            && !game.identification.is_known(name)
+        {
+            objects.unidentified.insert(id, appearance.clone());
+            objects.renderables[id].colour = DISGUISED_COLOUR;
+        }
+    }
+}
+
+/// Learn what every item called `name` is, wherever it is. Returns whether it wasn't known before
+pub fn identify(name: &str, game: &mut Game, objects: &mut Objects) -> bool {
+    let Some(appearance) = game.identification.appearances.get(name) else {
+        return false;
+    };
+    if !game.identification.known.insert(name.to_string()) {
+        return false;
+    }
+
+    game.messages.add(format!("The {} was a {}!", appearance, name), LIGHT_CYAN);
+    let colour = content().items.iter().find(|item| item.name == name).map(|item| item.colour);
+    for id in objects.unidentified.ids() {
+        if objects.names[id] == name {
+            objects.unidentified.remove(id);
+            if let Some(colour) = colour {
+                objects.renderables[id].colour = colour;
+            }
+        }
+    }
+    true
+}
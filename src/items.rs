@@ -1,174 +1,340 @@
-use tcod::colors::*;
-
-use crate::*;
-
-const HEAL_AMOUNT: i32 = 40;
-
-const LIGHTNING_DAMAGE: i32 = 20;
-const LIGHTNING_RANGE: i32 = 5;
-
-const CONFUSION_RANGE: i32 = 8;
-const CONFUSION_NUM_TURNS: i32 = 10;
-
-const FIREBALL_DAMAGE: i32 = 25;
-const FIREBALL_RADIUS: i32 = 3;
-
-
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub enum Item {
-    Heal,
-    Lightning,
-    Confuse,
-    Fireball,
-    Sword,
-    Shield,
-}
-
-/// Add to player's inventory and remove from map
-pub fn pick_item_up(object_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
-    if game.inventory.len() >= 26 {
-        game.messages.add(format!("Your inventory is full, cannot pick up {}", objects[object_id].name), RED.into());
-    } else {
-        let item = objects.swap_remove(object_id);
-        game.messages.add(format!("You picked up {}", item.name), GREEN.into());
-        let index = game.inventory.len();
-        let slot = item.equipment.map(|e| e.slot);
-        game.inventory.push(item);
-
-        // Automatically equip, if the current slot is unused
-        if let Some(slot) = slot {
-            if get_equipped_in_slot(slot, &game.inventory).is_none() {
-                game.inventory[index].equip(&mut game.messages);
-            }
-        }
-    }
-}
-
-pub enum UseResult {
-    UsedUp,
-    UsedAndKept,
-    Cancelled,
-}
-
-pub fn use_item(inventory_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) {
-    use Item::*;
-
-    // Call relative use function if defined
-    if let Some(item) = game.inventory[inventory_id].item {
-        let on_use: fn(usize, &mut Tcod, &mut Game, &mut [Object]) -> UseResult = match item {
-            Heal => cast_heal,
-            Lightning => cast_lightning,
-            Confuse => cast_confuse,
-            Fireball => cast_fireball,
-            Sword => toggle_equipment,
-            Shield => toggle_equipment,
-        };
-        match on_use(inventory_id, tcod, game, objects) {
-            UseResult::UsedUp => {
-                // Destroy after use
-                game.inventory.remove(inventory_id);
-            }
-            UseResult::UsedAndKept => {}    // No need for action
-            UseResult::Cancelled => {
-                game.messages.add("Cancelled", WHITE.into());
-            }
-        }
-    } else {
-        game.messages.add(format!("The {} cannot be used", game.inventory[inventory_id].name), WHITE.into());
-    }
-}
-
-pub fn drop_item(inventory_id: usize, game: &mut Game, objects: &mut Vec<Object>) {
-    let mut item = game.inventory.remove(inventory_id);
-    if item.equipment.is_some() { item.unequip(&mut game.messages); }   // Unequip item when dropped
-
-    item.set_pos(objects[PLAYER].x, objects[PLAYER].y);
-
-    game.messages.add(format!("You dropped your {}.", item.name), YELLOW.into());
-    objects.push(item);
-}
-
-
-
-//-----------------------------//
-// ITEM FUNCTIONS              //
-//-----------------------------//
-fn cast_heal(_inventory_id: usize, _tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> UseResult {
-    // Heal the player
-    let player = &mut objects[PLAYER];
-    if let Some(fighter) = player.fighter {
-        if fighter.hp == fighter.base_max_hp { 
-            game.messages.add("You are already at full health!", RED.into());
-            return UseResult::Cancelled;
-        }
-        game.messages.add("Your wounds are healed", LIGHT_VIOLET.into());
-        player.heal(HEAL_AMOUNT, game);
-        return UseResult::UsedUp;
-    }
-    UseResult::Cancelled
-}
-
-fn cast_lightning(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> UseResult {
-    // Find closest enemy (inside max range)
-    let monster_id = closest_monster(tcod, objects, LIGHTNING_RANGE);
-
-    if let Some(monster_id) = monster_id {
-        // Zap monster
-        game.messages.add(format!("A lightning bolt strikes the {} with a loud thunder! \n It takes {} damage", objects[monster_id].name, LIGHTNING_DAMAGE), LIGHT_BLUE.into());
-        if let Some(xp) = objects[monster_id].take_damage(LIGHTNING_DAMAGE, game) {
-            objects[PLAYER].fighter.as_mut().unwrap().xp += xp;
-        }
-        UseResult::UsedUp
-    } else {
-        // No enemy found within range
-        game.messages.add("No enemy is close enough to strike.", RED.into());
-        UseResult::Cancelled
-    }
-}
-
-fn cast_confuse(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> UseResult {
-    game.messages.add( "Left-click on an enemy to confuse it, or Right-click to cancel.", LIGHT_CYAN.into());
-    let monster_id = target_monster(tcod, game, objects, Some(CONFUSION_RANGE as f32));
-
-    if let Some(monster_id) = monster_id {
-        let old_ai = objects[monster_id].ai.take().unwrap_or(Ai::Basic);
-
-        // Replace AI with confused one
-        objects[monster_id].ai = Some(Ai::Confused { previous_ai: Box::new(old_ai), num_turns: CONFUSION_NUM_TURNS });
-        game.messages.add(format!("The eyes of the {} look vacant, as they start to stumble around", objects[monster_id].name), LIGHT_GREEN.into());
-        UseResult::UsedUp
-    } else {
-        // No enemy found in range
-        game.messages.add("No enemy is close enough to confuse", RED.into());
-        UseResult::Cancelled
-    }
-}
-
-fn cast_fireball(_inventory_id: usize, tcod: &mut Tcod, game: &mut Game, objects: &mut [Object]) -> UseResult {
-    // Ask the player for a target tile
-    game.messages.add("Left-click on a target tile for the fireball, or Right-click to cancel.", LIGHT_CYAN.into());
-    let (x, y) = match target_tile(tcod, game, objects, None) {
-        Some(tile_pos) => tile_pos,
-        None => return UseResult::Cancelled,
-    };
-    game.messages.add(format!("The fireball explodes, burning everything within {} tiles!", FIREBALL_RADIUS), ORANGE.into());
-
-    let mut xp_to_gain = 0;
-
-    for (id, obj) in objects.iter_mut().enumerate() {
-        if obj.distance(x, y) <= FIREBALL_RADIUS as f32 && obj.fighter.is_some() && id != PLAYER {
-            game.messages.add(
-                format!("The {} gets burned for {} hit points.", obj.name, FIREBALL_DAMAGE),
-                ORANGE.into(),
-            );
-
-            if let Some(xp) = obj.take_damage(FIREBALL_DAMAGE, game) {
-                xp_to_gain += xp; // Only add XP if it's a valid enemy
-            }
-        }
-    }
-    // Reward the player with accumulated XP
-    objects[PLAYER].fighter.as_mut().unwrap().xp += xp_to_gain;
-
-    UseResult::UsedUp
-}
\ No newline at end of file
+use crate::*;
+
+const INVENTORY_SIZE: usize = 26;   // One for each letter in the inventory menu
+
+const HEAL_AMOUNT: i32 = 40;
+
+const LIGHTNING_DAMAGE: i32 = 20;
+const LIGHTNING_RANGE: i32 = 5;
+
+const AFFLICT_RANGE: i32 = 8;
+
+const FIREBALL_DAMAGE: i32 = 25;
+const FIREBALL_RADIUS: i32 = 3;
+
+
+/// What using an item does. Everything else about it comes from its `ItemDef`
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Item {
+    Heal,
+    Lightning,
+    Fireball,
+    Equip,
+    /// Shot or thrown rather than used
+    Missile,
+    /// Puts a status effect on whoever drinks it
+    Drink(StatusEffect),
+    /// Puts a status effect on a monster of the player's choice
+    Afflict(StatusEffect),
+    /// Tells the player what another item is
+    Identify,
+}
+
+impl Item {
+    /// Whether items with this effect can share an inventory slot. Anything worn is kept apart
+    pub fn stacks(self) -> bool {
+        self != Item::Equip
+    }
+}
+
+/// Add to `owner`'s inventory and remove from map. Monsters are only heard of when they're seen
+pub fn pick_item_up(owner: ObjectId, object_id: ObjectId, game: &mut Game, objects: &mut Objects) {
+    let is_player = objects.players.contains(owner);
+    let message = if is_player {
+        Some(format!("You picked up {}", objects.display_name(object_id)))
+    } else {
+        let (x, y) = objects.pos(owner);
+        game.fov.is_in_fov(x, y).then(|| format!("The {} picks up {}.", objects.names[owner], objects.display_name(object_id)))
+    };
+
+    let inventory = &objects.inventories[owner];
+    if let Some(&stack_id) = inventory.iter().find(|&&item_id| objects.stacks_with(item_id, object_id)) {
+        // Joins a stack already carried, so needs no room of its own
+        if let Some(message) = message { game.messages.add(message, GREEN); }
+        objects.counts[stack_id] += objects.counts[object_id];
+        objects.despawn(object_id);
+    } else if inventory.len() >= INVENTORY_SIZE {
+        if is_player {
+            game.messages.add(format!("Your inventory is full, cannot pick up {}", objects.display_name(object_id)), RED);
+        }
+    } else {
+        objects.positions.remove(object_id);
+        objects.inventories[owner].push(object_id);
+        if let Some(message) = message { game.messages.add(message, GREEN); }
+
+        // Automatically equip, if the current slot is unused
+        if fits_free_slot(object_id, owner, objects) {
+            if is_player { equip(object_id, objects, &mut game.messages); }
+            else { objects.equipment[object_id].equipped = true; }
+        }
+    }
+}
+
+/// Have a monster drink something from its inventory that heals it. Returns whether it had anything
+pub fn drink_healing_potion(monster_id: ObjectId, game: &mut Game, objects: &mut Objects) -> bool {
+    let potion = objects.inventories.get(monster_id).and_then(|inventory| {
+        inventory.iter().copied().find(|&item_id| match objects.items.get(item_id) {
+            Some(Item::Heal) => true,
+            Some(Item::Drink(effect)) => effect.kind == StatusKind::Regenerating,
+            _ => false,
+        })
+    });
+    let Some(potion) = potion else {
+        return false;
+    };
+
+    let (x, y) = objects.pos(monster_id);
+    if game.fov.is_in_fov(x, y) {
+        // Seeing what it does is as good as drinking it
+        game.messages.add(format!("The {} drinks a {}.", objects.names[monster_id], objects.display_name(potion)), LIGHT_VIOLET);
+        let name = objects.names[potion].clone();
+        identify(&name, game, objects);
+    }
+    match objects.items[potion] {
+        Item::Drink(effect) => add_status(monster_id, effect, monster_id, game, objects),
+        _ => heal(monster_id, HEAL_AMOUNT, objects),
+    }
+    use_up_one(monster_id, potion, objects);
+    true
+}
+
+/// Take one item off a stack in `owner`'s inventory, destroying it if it was the last
+fn use_up_one(owner: ObjectId, item_id: ObjectId, objects: &mut Objects) {
+    if objects.counts.get(item_id).is_some_and(|&count| count > 1) {
+        objects.counts[item_id] -= 1;
+    } else {
+        objects.inventories[owner].retain(|&id| id != item_id);
+        objects.despawn(item_id);
+    }
+}
+
+/// Split `count` items off a stack into an item of their own, left wherever the stack is
+pub fn split_stack(item_id: ObjectId, count: u32, objects: &mut Objects) -> ObjectId {
+    objects.counts[item_id] -= count;
+    let split = objects.duplicate(item_id);
+    objects.counts[split] = count;
+    split
+}
+
+pub enum UseResult {
+    UsedUp,
+    UsedAndKept,
+    Cancelled,
+}
+
+/// Returns whether the item was used, and so took up the player's time
+pub fn use_item(inventory_index: usize, frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> bool {
+    use Item::*;
+
+    // Call relative use function if defined
+    let item_id = objects.inventories[PLAYER][inventory_index];
+    if let Some(&item) = objects.items.get(item_id) {
+        let on_use: fn(ObjectId, &mut dyn Frontend, &mut Game, &mut Objects) -> UseResult = match item {
+            Heal => cast_heal,
+            Lightning => cast_lightning,
+            Fireball => cast_fireball,
+            Equip => toggle_equipment,
+            Missile => aim_missile,
+            Drink(_) => cast_drink,
+            Afflict(_) => cast_afflict,
+            Identify => cast_identify,
+        };
+        // Whatever it was, using it gives it away
+        let name = objects.names[item_id].clone();
+        match on_use(item_id, frontend, game, objects) {
+            UseResult::UsedUp => {
+                identify(&name, game, objects);
+                use_up_one(PLAYER, item_id, objects);
+                true
+            }
+            UseResult::UsedAndKept => {
+                identify(&name, game, objects);
+                true
+            }
+            UseResult::Cancelled => {
+                game.messages.add("Cancelled", WHITE);
+                false
+            }
+        }
+    } else {
+        game.messages.add(format!("The {} cannot be used", objects.display_name(item_id)), WHITE);
+        false
+    }
+}
+
+/// Drop an item, or as many of a stack as the player asks for
+pub fn drop_item(inventory_index: usize, frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) {
+    let stack_id = objects.inventories[PLAYER][inventory_index];
+    let count = objects.counts.get(stack_id).copied().unwrap_or(1);
+    let dropped = if count > 1 {
+        let header = format!("Drop how many of your {}?", objects.display_name(stack_id));
+        match frontend.choose_quantity(&header, count) {
+            None | Some(0) => {
+                game.messages.add("Cancelled", WHITE);
+                return;
+            }
+            Some(quantity) if quantity < count => Some(quantity),
+            Some(_) => None,
+        }
+    } else {
+        None
+    };
+
+    // Dropping part of a stack leaves the rest in the inventory
+    let item_id = match dropped {
+        Some(quantity) => split_stack(stack_id, quantity, objects),
+        None => objects.inventories[PLAYER].remove(inventory_index),
+    };
+    if objects.equipment.contains(item_id) { unequip(item_id, objects, &mut game.messages); }   // Unequip item when dropped
+
+    let (x, y) = objects.pos(PLAYER);
+    objects.set_pos(item_id, x, y);
+
+    game.messages.add(format!("You dropped your {}.", objects.display_name(item_id)), YELLOW);
+}
+
+
+
+//-----------------------------//
+// ITEM FUNCTIONS              //
+//-----------------------------//
+fn cast_heal(_item_id: ObjectId, _frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    // Heal the player
+    if let Some(&fighter) = objects.fighters.get(PLAYER) {
+        if fighter.hp == fighter.base_max_hp { 
+            game.messages.add("You are already at full health!", RED);
+            return UseResult::Cancelled;
+        }
+        game.messages.add("Your wounds are healed", LIGHT_VIOLET);
+        heal(PLAYER, HEAL_AMOUNT, objects);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+fn aim_missile(item_id: ObjectId, _frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    // Missiles need a target, which `fire` and `throw` ask for
+    let (key, verb) = match objects.ranged.get(item_id) {
+        Some(Ranged::Ammo { .. }) => ("f", "fire"),
+        _ => ("t", "throw"),
+    };
+    game.messages.add(format!("Press {} to {} the {}.", key, verb, objects.names[item_id]), WHITE);
+    UseResult::Cancelled
+}
+
+fn cast_lightning(_item_id: ObjectId, _frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    // Find closest enemy (inside max range)
+    let monster_id = closest_monster(game, objects, LIGHTNING_RANGE);
+
+    if let Some(monster_id) = monster_id {
+        // Zap monster
+        game.messages.add(format!("A lightning bolt strikes the {} with a loud thunder! \n It takes {} damage", objects.names[monster_id], LIGHTNING_DAMAGE), LIGHT_BLUE);
+        if let Some(xp) = take_damage(monster_id, LIGHTNING_DAMAGE, game, objects) {
+            objects.fighters[PLAYER].xp += xp;
+        }
+        UseResult::UsedUp
+    } else {
+        // No enemy found within range
+        game.messages.add("No enemy is close enough to strike.", RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_drink(item_id: ObjectId, _frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    if let Some(&Item::Drink(effect)) = objects.items.get(item_id) {
+        add_status(PLAYER, effect, PLAYER, game, objects);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+fn cast_afflict(item_id: ObjectId, frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    let Some(&Item::Afflict(effect)) = objects.items.get(item_id) else {
+        return UseResult::Cancelled;
+    };
+    game.messages.add(format!("Left-click on an enemy to make it {}, or Right-click to cancel.", effect.kind), LIGHT_CYAN);
+    let monster_id = target_monster(frontend, game, objects, Some(AFFLICT_RANGE as f32));
+
+    if let Some(monster_id) = monster_id {
+        add_status(monster_id, effect, PLAYER, game, objects);
+        UseResult::UsedUp
+    } else {
+        // No enemy found in range
+        game.messages.add(format!("No enemy is close enough to make {}", effect.kind), RED);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_identify(item_id: ObjectId, frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    // Reading the scroll is enough to know what it is, so it's used up whatever gets chosen
+    let name = objects.names[item_id].clone();
+    identify(&name, game, objects);
+
+    let inventory_index = frontend.choose_inventory_item(objects, "Press the key next to an item to identify it, or any other to waste the scroll");
+    match inventory_index.map(|index| objects.inventories[PLAYER][index]) {
+        Some(chosen) if objects.unidentified.contains(chosen) => {
+            let name = objects.names[chosen].clone();
+            identify(&name, game, objects);
+        }
+        Some(_) => game.messages.add("You already knew what that was. The scroll crumbles away.", WHITE),
+        None => game.messages.add("The scroll crumbles away unused.", WHITE),
+    }
+    UseResult::UsedUp
+}
+
+fn cast_fireball(_item_id: ObjectId, frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> UseResult {
+    // Ask the player for a target tile
+    game.messages.add("Left-click on a target tile for the fireball, or Right-click to cancel.", LIGHT_CYAN);
+    let (x, y) = match target_tile(frontend, game, objects, None) {
+        Some(tile_pos) => tile_pos,
+        None => return UseResult::Cancelled,
+    };
+    game.messages.add(format!("The fireball explodes, burning everything within {} tiles!", FIREBALL_RADIUS), ORANGE);
+
+    let mut xp_to_gain = 0;
+
+    for id in objects.positions.ids() {
+        if objects.distance(id, x, y) <= FIREBALL_RADIUS as f32 && objects.fighters.contains(id) && !objects.players.contains(id) {
+            game.messages.add(
+                format!("The {} gets burned for {} hit points.", objects.names[id], FIREBALL_DAMAGE),
+                ORANGE,
+            );
+
+            if let Some(xp) = take_damage(id, FIREBALL_DAMAGE, game, objects) {
+                xp_to_gain += xp; // Only add XP if it's a valid enemy
+            }
+        }
+    }
+    // Reward the player with accumulated XP
+    objects.fighters[PLAYER].xp += xp_to_gain;
+
+    UseResult::UsedUp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A new game with a Scroll of Identify in the player's inventory, after the starting dagger
+    fn with_identify_scroll() -> (Game, Objects) {
+        load_content().expect("Content should load");
+        let (game, mut objects) = new_game(5);
+        let scroll = content().items.iter().find(|item| item.effect == Item::Identify).unwrap();
+        let scroll_id = create_item(scroll, &mut objects);
+        objects.inventories[PLAYER].push(scroll_id);
+        disguise_items(&game, &mut objects);
+        (game, objects)
+    }
+
+    #[test]
+    fn identify_scrolls_are_used_up_even_when_wasted() {
+        for choice in [None, Some(0)] {
+            let (mut game, mut objects) = with_identify_scroll();
+            let mut frontend = ScriptedFrontend::new(vec![Input::Inventory(choice)]);
+
+            assert!(use_item(1, &mut frontend, &mut game, &mut objects));
+            assert_eq!(frontend.error(), None);
+            assert_eq!(objects.inventories[PLAYER].len(), 1);
+        }
+    }
+}
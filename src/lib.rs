@@ -0,0 +1,423 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use rand::Rng;
+use tcod::{
+    console::*,
+    input::{Key, Mouse},
+};
+
+use crate::{
+    ai::*,
+    colour::*,
+    combat::*,
+    content::*,
+    death::*,
+    dice::*,
+    dungeon::*,
+    enchantment::*,
+    equipment::*,
+    fov::*,
+    frontend::*,
+    game::*,
+    gui::*,
+    identify::*,
+    items::*,
+    leveling::*,
+    map::*,
+    mapgen::*,
+    message::*,
+    movement::*,
+    object::*,
+    pathfinding::*,
+    ranged::*,
+    replay::*,
+    rng::*,
+    save::*,
+    schedule::*,
+    status::*,
+    store::*,
+    tile::*,
+};
+
+mod ai;
+mod colour;
+mod combat;
+mod content;
+mod death;
+mod dice;
+mod dungeon;
+mod enchantment;
+mod equipment;
+mod fov;
+mod frontend;
+mod game;
+mod gui;
+mod identify;
+mod items;
+mod leveling;
+mod map;
+mod mapgen;
+mod message;
+mod movement;
+mod object;
+mod pathfinding;
+mod ranged;
+mod replay;
+mod rng;
+mod save;
+mod schedule;
+mod status;
+mod store;
+mod tile;
+
+// What the binary and tests drive the game through
+pub use crate::{
+    content::load_content,
+    frontend::{Command, Input, ScriptedFrontend},
+    game::{new_game, play_game},
+    store::{Objects, PLAYER},
+};
+
+
+
+// Size of the screen
+const SCREEN_WIDTH: i32 = 80;
+const SCREEN_HEIGHT: i32 = 50;
+
+
+//-----------------------------//
+// GUI CONSTANTS               //
+//-----------------------------//
+const BAR_WIDTH: i32 = 20;
+const PANEL_HEIGHT: i32 = 7;
+const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
+
+const LEVEL_SCREEN_WIDTH: i32 = 40;
+const CHARACTER_SCREEN_WIDTH: i32 = 30;
+const EQUIPMENT_SCREEN_WIDTH: i32 = 40;
+const COMBAT_LOG_WIDTH: i32 = 70;
+const COMBAT_LOG_LINES: usize = 20;     // How many of the latest rolls the combat log shows
+const INVENTORY_WIDTH: i32 = 50;
+const QUANTITY_WIDTH: i32 = 40;
+const SAVE_SLOT_WIDTH: i32 = 70;
+const SLOTS_PER_PAGE: usize = 24;     // Leaves two of the menu's letters for turning the page
+
+const MSG_X: i32 = BAR_WIDTH + 2;
+const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
+const MSG_HEIGHT: usize = PANEL_HEIGHT as usize - 1;
+
+
+//-----------------------------//
+// MAP CONSTANTS               //
+//-----------------------------//
+const MAP_WIDTH: i32 = 80;
+const MAP_HEIGHT: i32 = 43;     // Space for panels with stats
+const COLOUR_DARK_WALL: SerializableColour = SerializableColour { r: 0, g: 0, b: 100 };
+const COLOUR_LIGHT_WALL: SerializableColour = SerializableColour { r: 130, g: 110, b: 50 };
+const COLOUR_DARK_GROUND: SerializableColour = SerializableColour { r: 50, g: 50, b: 150 };
+const COLOUR_LIGHT_GROUND: SerializableColour = SerializableColour { r: 200, g: 180, b: 50, };
+
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MIN_ROOMS: i32 = 5;
+const MAX_ROOMS: i32 = 30;
+const ROOMS_PER_LEVEL: i32 = 1;         // Extra rooms aimed for on each floor down
+const ROOM_PLACEMENT_ATTEMPTS: u32 = 500;
+
+const BSP_MIN_LEAF_SIZE: i32 = 12;
+const CAVE_WALL_CHANCE: u32 = 45;       // Percentage of tiles starting as wall
+const CAVE_SMOOTHING_STEPS: u32 = 4;
+const CAVE_ATTEMPTS: u32 = 10;         // Fresh noise tried before falling back to rooms
+const DRUNKARD_FLOOR_PERCENT: i32 = 40;
+const DRUNKARD_STEPS: u32 = 200;        // Steps each walker takes before the next one starts
+const SPAWN_REGION_SIZE: i32 = 10;      // Open maps are split into squares this big to spawn in
+
+
+//-----------------------------//
+// PLAYER CONSTANTS            //
+//-----------------------------//
+const LEVEL_UP_BASE: i32 = 200;
+const LEVEL_UP_FACTOR: i32 = 150;
+
+
+//-----------------------------//
+// AI CONSTANTS                //
+//-----------------------------//
+const CROWDED_STEP_COST: u32 = 5;   // Extra cost for a path through a monster, which may move out of the way
+const MONSTER_MEMORY_TURNS: i32 = 20;   // How long a monster follows the player's trail once out of sight
+const WANDER_RADIUS: i32 = 8;
+const WANDER_ATTEMPTS: u32 = 10;
+const KEEP_DISTANCE: i32 = 3;       // Monsters with a ranged attack back away from a player closer than this
+const MONSTER_HEAL_PERCENT: i32 = 40;   // Monsters drink a healing potion once down to this much of their max HP
+
+
+//-----------------------------//
+// STEALTH CONSTANTS           //
+//-----------------------------//
+const WAIT_NOISE: i32 = 1;      // How far away each action can be heard, in tiles
+const WALK_NOISE: i32 = 4;
+const FIGHT_NOISE: i32 = 10;
+const SHOOT_NOISE: i32 = 2;
+const SLEEP_CHANCE: u32 = 40;   // Percentage of monsters found asleep
+const AMBUSH_DAMAGE_MULTIPLIER: i32 = 2;
+
+
+//-----------------------------//
+// ITEM CONSTANTS              //
+//-----------------------------//
+const MAGIC_THRESHOLD: u32 = 70;        // Rarity rolls, out of 100 plus the depth bonus, at or over these make better items
+const RARE_THRESHOLD: u32 = 90;
+const LEGENDARY_THRESHOLD: u32 = 100;
+const RARITY_PER_LEVEL: u32 = 1;        // Added to rarity rolls for each floor down
+const ENCHANTMENT_DEPTH_STEP: u32 = 3;  // Floors for each enchantment level items can have
+const LEGENDARY_ENCHANTMENT_BONUS: i32 = 2;
+const ENCHANTMENT_HP: i32 = 5;          // Max HP each enchantment level gives items that only give HP
+
+
+//-----------------------------//
+// COMBAT CONSTANTS            //
+//-----------------------------//
+const BASE_HIT_CHANCE: i32 = 75;        // Percentage chance to hit a target whose evasion matches the attack
+const HIT_CHANCE_PER_POINT: i32 = 3;    // Gained for each point of attack over the target's evasion, lost for each under
+const MIN_HIT_CHANCE: i32 = 5;
+const MAX_HIT_CHANCE: i32 = 95;
+const CRIT_CHANCE: i32 = 5;             // Percentage of attacks that hit critically, whatever the odds
+const CRIT_MULTIPLIER: i32 = 2;
+const UNARMED_DAMAGE: Dice = Dice { count: 1, sides: 3, bonus: 0 };
+
+
+//-----------------------------//
+// TIME CONSTANTS              //
+//-----------------------------//
+const NORMAL_SPEED: i32 = 10;       // Energy gained each tick
+const TICKS_PER_TURN: u32 = 10;     // Ticks an ordinary action takes at normal speed. Status effects count in these turns
+const MOVE_COST: i32 = 100;         // Energy used up by each kind of action
+const ATTACK_COST: i32 = 100;
+const SHOOT_COST: i32 = 120;
+const USE_ITEM_COST: i32 = 50;
+const WAIT_COST: i32 = 100;
+
+
+//-----------------------------//
+// FOV CONSTANTS               //
+//-----------------------------//
+const FOV_LIGHT_WALLS: bool = true;     // To light walls or not
+const TORCH_RADIUS: i32 = 10;
+
+const LIMIT_FPS: i32 = 20;      // FPS maximum
+
+
+type Map = Vec<Vec<tile::Tile>>;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Game {
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    pub map: Map,
+    pub messages: Messages,
+    pub combat_log: Messages,   // How every roll in a fight came out
+    pub dungeon_level: u32,
+    pub dungeon: Dungeon,
+    pub turns: u32,
+    pub ticks: u32,     // Time passed, which turns make up more or less of depending on speed
+    pub rng: GameRng,
+    pub identification: Identification,
+    #[serde(skip)]
+    pub fov: FovMap,
+    #[serde(skip)]
+    pub noise: i32,     // How far away the player's last action could be heard
+
+}
+
+struct Tcod {
+    root: Root,
+    con: Offscreen,
+    panel: Offscreen,
+    key: Key,
+    mouse: Mouse,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlayerAction {
+    TookTurn(i32),      // With how much energy the action used up
+    DidntTakeTurn,
+    Exit,
+}
+
+
+fn handle_command(command: Command, frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> PlayerAction {
+    use PlayerAction::*;
+
+    let player_alive = objects.is_alive(PLAYER);
+    match (command, player_alive) {
+        (Command::Exit, _) => Exit,    // Exit game
+
+        // Movement
+        (Command::Move { dx, dy }, true) => {
+            TookTurn(player_move_or_attack(dx, dy, game, objects))
+        }
+        (Command::Wait, true) => {
+            game.noise = WAIT_NOISE;
+            TookTurn(WAIT_COST) // Do nothing, i.e. wait for the monster to come to you
+        }
+
+        // Pick up an item
+        (Command::PickUp, true) => {
+            let (x, y) = objects.pos(PLAYER);
+            let item_id = objects.at(x, y).find(|&id| objects.items.contains(id));
+
+            if let Some(item_id) = item_id {
+                pick_item_up(PLAYER, item_id, game, objects);
+            }
+            DidntTakeTurn
+        }
+
+        // Access inventory
+        (Command::UseItem, true) => {
+            // Show inventory
+            let inventory_index = frontend.choose_inventory_item(objects, "Press the key next to an item to use it, or any other to cancel");
+
+            match inventory_index {
+                Some(inventory_index) if use_item(inventory_index, frontend, game, objects) => TookTurn(USE_ITEM_COST),
+                _ => DidntTakeTurn,
+            }
+        }
+
+        // Drop item from inventory
+        (Command::DropItem, true) => {
+            // Show the inventory, drop item if selected
+            let inventory_index = frontend.choose_inventory_item(objects, "Press the key next to an item to drop it, or any other to cancel.\n");
+            if let Some(inventory_index) = inventory_index {
+                drop_item(inventory_index, frontend, game, objects);
+            }
+            DidntTakeTurn
+        }
+
+        // Shoot the equipped launcher, or throw something
+        (Command::Fire, true) => {
+            if fire(frontend, game, objects) { TookTurn(SHOOT_COST) } else { DidntTakeTurn }
+        }
+        (Command::Throw, true) => {
+            if throw(frontend, game, objects) { TookTurn(SHOOT_COST) } else { DidntTakeTurn }
+        }
+
+        // Access Stairs
+        (Command::TakeStairs, true) => {
+            // Go up or down, depending on the stairs
+            take_stairs(game, objects);
+            DidntTakeTurn
+        }
+
+        // Show what is worn where
+        (Command::EquipmentScreen, true) => {
+            frontend.msgbox(&format!("Equipment\n\n{}", paper_doll(PLAYER, objects)), EQUIPMENT_SCREEN_WIDTH);
+            DidntTakeTurn
+        }
+
+        // Show how the latest fights went, roll by roll
+        (Command::CombatLog, _) => {
+            let mut lines: Vec<&str> = game.combat_log.iter().rev().take(COMBAT_LOG_LINES).map(|(line, _)| line.as_str()).collect();
+            lines.reverse();
+            let text = if lines.is_empty() { "Nothing has been fought yet.".to_string() } else { lines.join("\n") };
+            frontend.msgbox(&format!("Combat log\n\n{}", text), COMBAT_LOG_WIDTH);
+            DidntTakeTurn
+        }
+
+        // Show stats
+        (Command::CharacterInfo, true) => {
+            // show character information
+            if let Some(fighter) = objects.fighters.get(PLAYER) {
+                let level_up_xp = LEVEL_UP_BASE + fighter.level as i32 * LEVEL_UP_FACTOR;
+                let msg = format!(
+                    "Character information
+        
+        Seed: {}
+        Level: {}
+        Experience: {}
+        Experience to level up: {}
+        
+        Maximum HP: {}
+        Attack: {}
+        Defense: {}
+        Evasion: {}",
+                    game.rng.seed(), fighter.level, fighter.xp, level_up_xp, objects.max_hp(PLAYER), objects.power(PLAYER), objects.defense(PLAYER), objects.evasion(PLAYER)
+                );
+                frontend.msgbox(&msg, CHARACTER_SCREEN_WIDTH);
+            }
+        
+            DidntTakeTurn
+        }
+
+        _ => DidntTakeTurn,
+    }
+}
+
+/// Play a new game without a window, feeding it the inputs from a JSON script,
+/// then print the final game state so it can be checked
+fn run_headless(script_path: &str, seed: u64) -> Result<(), Box<dyn Error>> {
+    let mut script = String::new();
+    File::open(script_path)?.read_to_string(&mut script)?;
+    let inputs: Vec<Input> = serde_json::from_str(&script)?;
+
+    let mut frontend = ScriptedFrontend::new(inputs);
+    let (mut game, mut objects) = new_game(seed);
+    play_game(&mut frontend, &mut game, &mut objects);
+    if let Some(error) = frontend.error() {
+        return Err(error.into());
+    }
+
+    println!("{}", serde_json::to_string(&(&game, &objects))?);
+    Ok(())
+}
+
+/// Start the game as asked for on the command line
+pub fn run() {
+    // Bad content is caught before anything else happens
+    if let Err(e) = load_content() {
+        eprintln!("Could not load game content: {}", e);
+        std::process::exit(1);
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, flag, script_path, rest @ ..] = args.as_slice() && flag == "--headless" {
+        // Runs are reproducible with an explicit seed: `--headless <script> [seed]`
+        let seed = match rest.first() {
+            Some(seed) => seed.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid seed: {}", seed);
+                std::process::exit(1);
+            }),
+            None => random_seed(),
+        };
+        if let Err(e) = run_headless(script_path, seed) {
+            eprintln!("Headless run failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    tcod::system::set_fps(LIMIT_FPS);
+
+    let root = Root::initializer()
+        .font("arial10x10.png", FontLayout::Tcod)
+        .font_type(FontType::Greyscale)
+        .size(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .title("Roguelike")
+        .init();
+
+    let mut tcod = Tcod { 
+        root, 
+        con: Offscreen::new(MAP_WIDTH, MAP_HEIGHT), 
+        panel: Offscreen::new(SCREEN_WIDTH, PANEL_HEIGHT),
+        key: Default::default(),
+        mouse: Default::default(), 
+    };
+
+    // Watch a recorded session straight away: `--replay <file>`
+    if let [_, flag, replay_path] = args.as_slice() && flag == "--replay" {
+        watch_replay(&mut tcod, replay_path);
+        return;
+    }
+
+   main_menu(&mut tcod);
+}
\ No newline at end of file
@@ -1,148 +1,269 @@
-use tcod::colors::*;
-use crate::*;
-use rand::Rng;
-use std::cmp;
-
-pub fn make_map(objects: &mut Vec<Object>, level: u32) -> Map {
-    let mut map = vec![vec![tile::Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
-    let mut rooms = vec![];
-
-    // Ensures no objects are lodged in walls when making new level
-    // ! Works only when the player is the first object
-    assert_eq!(&objects[PLAYER] as *const _, &objects[0] as *const _);
-    objects.truncate(1);
-
-    let mut room_count = 0;
-    while room_count < MIN_ROOMS && room_count < MAX_ROOMS {
-        // Generate random room dimensions
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-
-        // Generate random room position
-        let x = rand::thread_rng().gen_range(0, MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT - h);
-
-        let new_room = tile::Rect::new(x, y, w, h);
-        let failed = rooms.iter().any(|other_room| new_room.intersects_with(other_room));
-
-        if !failed {
-            create_room(new_room, &mut map);
-            place_objects(new_room, &map, objects, level);
-            let (new_x, new_y) = new_room.center(); 
-
-            if rooms.is_empty() {
-                // First room, place the player here
-                objects[PLAYER].set_pos(new_x, new_y);
-            } else {
-                // Connect new room to previous room
-                let (prev_x, prev_y) = rooms.last().unwrap().center();
-                connect_rooms(prev_x, prev_y, new_x, new_y, &mut map);
-            }
-
-            rooms.push(new_room);
-            room_count += 1;
-        }
-    }
-
-    // Ensure at least one room exists before placing stairs
-    if let Some(last_room) = rooms.last() {
-        let (last_room_x, last_room_y) = last_room.center();
-        let mut stairs = Object::new(last_room_x, last_room_y, '<', WHITE.into(), "Stairs", false);
-        stairs.always_visible = true;
-        objects.push(stairs);
-    } else {
-        panic!("No rooms were placed! Unable to generate stairs.");
-    }
-
-    // Verify all rooms are connected
-    ensure_map_connectivity(&mut map, &rooms);
-
-    map
-}
-
-pub fn create_room(room: tile::Rect, map: &mut Map) {
-    for x in (room.x1 + 1)..room.x2 {
-        for y in (room.y1 + 1)..room.y2 {
-            map[x as usize][y as usize] = tile::Tile::empty();
-        }
-    }
-}
-
-pub fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
-    for x in cmp::min(x1, x2)..=cmp::max(x1, x2) {
-        map[x as usize][y as usize] = tile::Tile::empty();
-    }
-}
-
-pub fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
-    for y in cmp::min(y1, y2)..=cmp::max(y1, y2) {
-        map[x as usize][y as usize] = tile::Tile::empty();
-    }
-}
-
-pub fn connect_rooms(x1: i32, y1: i32, x2: i32, y2: i32, map: &mut Map) {
-    if rand::random() {
-        create_h_tunnel(x1, x2, y1, map);
-        create_v_tunnel(y1, y2, x2, map);
-    } else {
-        create_v_tunnel(y1, y2, x1, map);
-        create_h_tunnel(x1, x2, y2, map);
-    }
-}
-
-pub fn ensure_map_connectivity(map: &mut Map, rooms: &Vec<tile::Rect>) {
-    let mut visited = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
-
-    // Flood fill from the player's starting room
-    let (start_x, start_y) = rooms[0].center();
-    flood_fill(start_x, start_y, map, &mut visited);
-
-    for room in rooms {
-        let (room_x, room_y) = room.center();
-        if !visited[room_x as usize][room_y as usize] {
-            // If room is not connected, force-connect it
-            let (closest_x, closest_y) = find_closest_connected_tile(room_x, room_y, &visited);
-            connect_rooms(closest_x, closest_y, room_x, room_y, map);
-        }
-    }
-}
-
-fn flood_fill(x: i32, y: i32, map: &Map, visited: &mut Vec<Vec<bool>>) {
-    let mut stack = vec![(x, y)];
-    while let Some((cx, cy)) = stack.pop() {
-        if cx < 0 || cy < 0 || cx >= MAP_WIDTH || cy >= MAP_HEIGHT {
-            continue;
-        }
-        if visited[cx as usize][cy as usize] || !map[cx as usize][cy as usize].is_walkable() {
-            continue;
-        }
-
-        visited[cx as usize][cy as usize] = true;
-        stack.push((cx + 1, cy));
-        stack.push((cx - 1, cy));
-        stack.push((cx, cy + 1));
-        stack.push((cx, cy - 1));
-    }
-}
-
-fn find_closest_connected_tile(x: i32, y: i32, visited: &Vec<Vec<bool>>) -> (i32, i32) {
-    let mut queue = vec![(x, y)];
-    while let Some((cx, cy)) = queue.pop() {
-        if visited[cx as usize][cy as usize] {
-            return (cx, cy);
-        }
-        if cx > 0 {
-            queue.push((cx - 1, cy));
-        }
-        if cy > 0 {
-            queue.push((cx, cy - 1));
-        }
-        if cx < MAP_WIDTH - 1 {
-            queue.push((cx + 1, cy));
-        }
-        if cy < MAP_HEIGHT - 1 {
-            queue.push((cx, cy + 1));
-        }
-    }
-    (x, y)
-}
+use crate::*;
+use rand::Rng;
+use std::cmp;
+
+/// Generate a floor with a layout chosen for its depth, then fill it with monsters, items and stairs
+pub fn make_map(objects: &mut Objects, level: u32, rng: &mut GameRng, messages: &mut Messages) -> Map {
+    let generated = generator_for_level(level, rng).generate(level, rng);
+    if generated.rooms_placed < generated.rooms_wanted {
+        messages.add(
+            format!("Level {}: only {} of {} rooms fitted.", level, generated.rooms_placed, generated.rooms_wanted),
+            LIGHT_GREY,
+        );
+    }
+
+    // The player goes in first, so nothing spawns on top of them
+    let (start_x, start_y) = generated.up_stairs;
+    objects.set_pos(PLAYER, start_x, start_y);
+
+    // Populate as many regions as the floor aims to have rooms, so how crowded it is doesn't depend on its layout
+    let mut spawn_regions = generated.spawn_regions;
+    while spawn_regions.len() > target_room_count(level) {
+        let index = rng.gen_range(0, spawn_regions.len());
+        spawn_regions.swap_remove(index);
+    }
+    for region in spawn_regions {
+        place_objects(region, &generated.map, objects, level, rng);
+    }
+
+    // The way back up, from every floor but the first
+    if level > 1 {
+        spawn_stairs(start_x, start_y, '<', UP_STAIRS, objects);
+    }
+
+    let (stairs_x, stairs_y) = generated.down_stairs;
+    spawn_stairs(stairs_x, stairs_y, '>', DOWN_STAIRS, objects);
+
+    generated.map
+}
+
+fn spawn_stairs(x: i32, y: i32, char: char, name: &str, objects: &mut Objects) {
+    let stairs = objects.spawn(name);
+    objects.set_pos(stairs, x, y);
+    objects.renderables.insert(stairs, Renderable { char, colour: WHITE, always_visible: true });
+}
+
+pub fn create_room(room: tile::Rect, map: &mut Map) {
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            map[x as usize][y as usize] = tile::Tile::empty();
+        }
+    }
+}
+
+pub fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
+    for x in cmp::min(x1, x2)..=cmp::max(x1, x2) {
+        map[x as usize][y as usize] = tile::Tile::empty();
+    }
+}
+
+pub fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
+    for y in cmp::min(y1, y2)..=cmp::max(y1, y2) {
+        map[x as usize][y as usize] = tile::Tile::empty();
+    }
+}
+
+pub fn connect_rooms(x1: i32, y1: i32, x2: i32, y2: i32, map: &mut Map, rng: &mut GameRng) {
+    if rng.gen_weighted_bool(2) {
+        create_h_tunnel(x1, x2, y1, map);
+        create_v_tunnel(y1, y2, x2, map);
+    } else {
+        create_v_tunnel(y1, y2, x1, map);
+        create_h_tunnel(x1, x2, y2, map);
+    }
+}
+
+pub fn ensure_map_connectivity(map: &mut Map, rooms: &Vec<tile::Rect>, rng: &mut GameRng) {
+    let mut visited = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+
+    // Flood fill from the player's starting room
+    let (start_x, start_y) = rooms[0].center();
+    flood_fill(start_x, start_y, map, &mut visited);
+
+    for room in rooms {
+        let (room_x, room_y) = room.center();
+        if !visited[room_x as usize][room_y as usize] {
+            // If room is not connected, force-connect it
+            let (closest_x, closest_y) = find_closest_connected_tile(room_x, room_y, &visited);
+            connect_rooms(closest_x, closest_y, room_x, room_y, map, rng);
+        }
+    }
+}
+
+fn flood_fill(x: i32, y: i32, map: &Map, visited: &mut [Vec<bool>]) {
+    let mut stack = vec![(x, y)];
+    while let Some((cx, cy)) = stack.pop() {
+        if cx < 0 || cy < 0 || cx >= MAP_WIDTH || cy >= MAP_HEIGHT {
+            continue;
+        }
+        if visited[cx as usize][cy as usize] || !map[cx as usize][cy as usize].is_walkable() {
+            continue;
+        }
+
+        visited[cx as usize][cy as usize] = true;
+        stack.push((cx + 1, cy));
+        stack.push((cx - 1, cy));
+        stack.push((cx, cy + 1));
+        stack.push((cx, cy - 1));
+    }
+}
+
+fn find_closest_connected_tile(x: i32, y: i32, visited: &[Vec<bool>]) -> (i32, i32) {
+    let mut queue = vec![(x, y)];
+    while let Some((cx, cy)) = queue.pop() {
+        if visited[cx as usize][cy as usize] {
+            return (cx, cy);
+        }
+        if cx > 0 {
+            queue.push((cx - 1, cy));
+        }
+        if cy > 0 {
+            queue.push((cx, cy - 1));
+        }
+        if cx < MAP_WIDTH - 1 {
+            queue.push((cx + 1, cy));
+        }
+        if cy < MAP_HEIGHT - 1 {
+            queue.push((cx, cy + 1));
+        }
+    }
+    (x, y)
+}
+
+
+//-----------------------------//
+// COMPACT SERIALIZATION       //
+//-----------------------------//
+/// Compact form of a map for binary saves: wall flags are packed two bits per tile
+/// and the explored state is run-length encoded, as explored tiles come in large patches
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PackedMap {
+    width: u32,
+    height: u32,
+    flags: Vec<u8>,             // (blocked, block_sight) for four tiles per byte, column by column
+    explored_runs: Vec<u32>,    // Alternating runs of unexplored and explored tiles, unexplored first
+}
+
+/// Either form a map can be read back from
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum MapRepr {
+    Tiles(Map),
+    Packed(PackedMap),
+}
+
+impl PackedMap {
+    fn pack(map: &Map) -> Self {
+        let mut flags = vec![];
+        let mut explored_runs = vec![];
+        let mut run = (false, 0);
+
+        for (index, tile) in map.iter().flatten().enumerate() {
+            if index % 4 == 0 {
+                flags.push(0);
+            }
+            let bits = (tile.blocked as u8) | ((tile.block_sight as u8) << 1);
+            *flags.last_mut().unwrap() |= bits << (2 * (index % 4));
+
+            if tile.explored != run.0 {
+                explored_runs.push(run.1);
+                run = (tile.explored, 0);
+            }
+            run.1 += 1;
+        }
+        explored_runs.push(run.1);
+
+        PackedMap {
+            width: map.len() as u32,
+            height: map.first().map_or(0, |column| column.len()) as u32,
+            flags,
+            explored_runs,
+        }
+    }
+
+    fn unpack(self) -> Map {
+        let explored = self
+            .explored_runs
+            .iter()
+            .enumerate()
+            .flat_map(|(run, &length)| std::iter::repeat_n(run % 2 == 1, length as usize));
+
+        let mut tiles = explored.enumerate().map(|(index, explored)| {
+            let bits = self.flags.get(index / 4).map_or(0, |byte| byte >> (2 * (index % 4)));
+            Tile {
+                blocked: bits & 1 != 0,
+                block_sight: bits & 2 != 0,
+                explored,
+            }
+        });
+
+        (0..self.width)
+            .map(|_| (0..self.height).map(|_| tiles.next().unwrap_or_else(Tile::wall)).collect())
+            .collect()
+    }
+}
+
+/// Human readable formats (JSON) get the map tile by tile, binary ones get a `PackedMap`
+pub fn serialize_map<S: serde::Serializer>(map: &Map, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::Serialize;
+
+    if serializer.is_human_readable() {
+        map.serialize(serializer)
+    } else {
+        PackedMap::pack(map).serialize(serializer)
+    }
+}
+
+pub fn deserialize_map<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Map, D::Error> {
+    use serde::Deserialize;
+
+    match MapRepr::deserialize(deserializer)? {
+        MapRepr::Tiles(map) => Ok(map),
+        MapRepr::Packed(packed) => Ok(packed.unpack()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(map: &Map) -> Vec<(bool, bool, bool)> {
+        map.iter().flatten().map(|tile| (tile.blocked, tile.block_sight, tile.explored)).collect()
+    }
+
+    #[test]
+    fn packed_maps_unpack_to_the_same_tiles() {
+        // An odd number of tiles leaves the last flag byte part full. The explored patch at the
+        // start makes the first unexplored run empty, and the map ends on another one
+        let explored = |index: usize| index < 3 || index == 10 || index >= 20;
+        let map: Map = (0..7)
+            .map(|x| {
+                (0..5)
+                    .map(|y| Tile {
+                        blocked: (x + y) % 3 == 0,
+                        block_sight: (x + y) % 3 == 0 && x % 2 == 0,
+                        explored: explored(x * 5 + y),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let packed = PackedMap::pack(&map);
+        assert_eq!(packed.explored_runs, vec![0, 3, 7, 1, 9, 15]);
+        assert_eq!(packed.flags.len(), 9);
+
+        let unpacked = packed.unpack();
+        assert_eq!(unpacked.len(), 7);
+        assert_eq!(tiles(&unpacked), tiles(&map));
+    }
+
+    #[test]
+    fn unexplored_maps_pack_to_a_single_run() {
+        let map: Map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+
+        let packed = PackedMap::pack(&map);
+        assert_eq!(packed.explored_runs, vec![(MAP_WIDTH * MAP_HEIGHT) as u32]);
+        assert_eq!(tiles(&packed.unpack()), tiles(&map));
+    }
+}
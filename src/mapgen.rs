@@ -0,0 +1,377 @@
+use std::cmp;
+use std::collections::VecDeque;
+
+use rand::{distributions::{IndependentSample, Weighted, WeightedChoice}, Rng};
+
+use crate::*;
+
+/// A freshly generated floor, before anything is placed on it
+pub struct GeneratedMap {
+    pub map: Map,
+    pub spawn_regions: Vec<Rect>,   // Areas monsters and items may be placed in, e.g. rooms
+    pub up_stairs: (i32, i32),      // Where the player arrives
+    pub down_stairs: (i32, i32),
+    pub rooms_wanted: usize,        // Rooms the generator aimed for, zero for layouts without rooms
+    pub rooms_placed: usize,        // Fewer than wanted when the map got too crowded to fit them all
+}
+
+/// Builds the layout of a floor at depth `level`. Every random choice must come from `rng`,
+/// so a floor still follows from the game's seed
+pub trait MapGenerator {
+    fn generate(&self, level: u32, rng: &mut GameRng) -> GeneratedMap;
+}
+
+/// Rooms a floor aims for, from `MIN_ROOMS` on the first floor up to `MAX_ROOMS` deeper down.
+/// Floors without rooms get as many populated areas instead
+pub fn target_room_count(level: u32) -> usize {
+    cmp::min(MIN_ROOMS + (level as i32 - 1) * ROOMS_PER_LEVEL, MAX_ROOMS) as usize
+}
+
+/// Pick the generator for a floor. The first floors are always rooms, stranger layouts show up deeper
+pub fn generator_for_level(level: u32, rng: &mut GameRng) -> &'static dyn MapGenerator {
+    let mut generator_choices = vec![
+        Weighted {
+            weight: from_dungeon_level(&[
+                Transition { level: 1, value: 100 },
+                Transition { level: 3, value: 50 },
+                Transition { level: 6, value: 25 },
+            ], level),
+            item: &RoomsAndCorridors as &dyn MapGenerator,
+        },
+        Weighted { weight: from_dungeon_level(&[Transition { level: 2, value: 40 }], level), item: &Bsp },
+        Weighted { weight: from_dungeon_level(&[Transition { level: 3, value: 25 }], level), item: &Caves },
+        Weighted { weight: from_dungeon_level(&[Transition { level: 5, value: 25 }], level), item: &DrunkardsWalk },
+    ];
+    WeightedChoice::new(&mut generator_choices).ind_sample(rng)
+}
+
+
+//-----------------------------//
+// ROOMS AND CORRIDORS         //
+//-----------------------------//
+/// Rooms dropped at random where they fit, each joined to the one before it
+pub struct RoomsAndCorridors;
+
+impl MapGenerator for RoomsAndCorridors {
+    fn generate(&self, level: u32, rng: &mut GameRng) -> GeneratedMap {
+        place_rooms(target_room_count(level), rng)
+    }
+}
+
+/// Try to fit `target` rooms, reporting how many actually made it in
+fn place_rooms(target: usize, rng: &mut GameRng) -> GeneratedMap {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut rooms: Vec<Rect> = vec![];
+
+    // Every try uses up an attempt, so a crowded map gives up rather than looping forever.
+    // The first try always fits, so there is at least one room
+    for _ in 0..ROOM_PLACEMENT_ATTEMPTS {
+        if rooms.len() >= target {
+            break;
+        }
+
+        // Generate random room dimensions
+        let w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+        let h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+
+        // Generate random room position
+        let x = rng.gen_range(0, MAP_WIDTH - w);
+        let y = rng.gen_range(0, MAP_HEIGHT - h);
+
+        let new_room = Rect::new(x, y, w, h);
+        let failed = rooms.iter().any(|other_room| new_room.intersects_with(other_room));
+
+        if !failed {
+            create_room(new_room, &mut map);
+
+            // Connect new room to previous room
+            if let Some(previous_room) = rooms.last() {
+                let (prev_x, prev_y) = previous_room.center();
+                let (new_x, new_y) = new_room.center();
+                connect_rooms(prev_x, prev_y, new_x, new_y, &mut map, rng);
+            }
+
+            rooms.push(new_room);
+        }
+    }
+
+    // Verify all rooms are connected
+    ensure_map_connectivity(&mut map, &rooms, rng);
+
+    GeneratedMap {
+        up_stairs: rooms[0].center(),
+        down_stairs: rooms[rooms.len() - 1].center(),
+        rooms_wanted: target,
+        rooms_placed: rooms.len(),
+        spawn_regions: rooms,
+        map,
+    }
+}
+
+
+//-----------------------------//
+// BSP                         //
+//-----------------------------//
+/// The map is cut in two again and again, one room goes in each piece and the pieces are joined back up
+/// the way they were cut. Gives evenly spread rooms with no overlaps
+pub struct Bsp;
+
+impl MapGenerator for Bsp {
+    fn generate(&self, _level: u32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        let rooms = bsp_split(Rect::new(0, 0, MAP_WIDTH - 1, MAP_HEIGHT - 1), &mut map, rng);
+
+        // Every leaf gets a room, so there is no count to fall short of
+        GeneratedMap {
+            up_stairs: rooms[0].center(),
+            down_stairs: rooms[rooms.len() - 1].center(),
+            rooms_wanted: rooms.len(),
+            rooms_placed: rooms.len(),
+            spawn_regions: rooms,
+            map,
+        }
+    }
+}
+
+/// Split `area` until the pieces are too small to split again and carve a room in each.
+/// Both halves of every split are connected, so the whole area ends up connected.
+/// Returns the rooms carved, from one corner of the area to the other
+fn bsp_split(area: Rect, map: &mut Map, rng: &mut GameRng) -> Vec<Rect> {
+    let width = area.x2 - area.x1;
+    let height = area.y2 - area.y1;
+    let split_vertically = match (width >= 2 * BSP_MIN_LEAF_SIZE, height >= 2 * BSP_MIN_LEAF_SIZE) {
+        (false, false) => {
+            // A leaf: the room fills a random part of it, its walls staying inside the leaf
+            let w = rng.gen_range(ROOM_MIN_SIZE, cmp::min(width, ROOM_MAX_SIZE) + 1);
+            let h = rng.gen_range(ROOM_MIN_SIZE, cmp::min(height, ROOM_MAX_SIZE) + 1);
+            let x = rng.gen_range(area.x1, area.x2 - w + 1);
+            let y = rng.gen_range(area.y1, area.y2 - h + 1);
+            let room = Rect::new(x, y, w, h);
+            create_room(room, map);
+            return vec![room];
+        }
+        (true, false) => true,
+        (false, true) => false,
+        (true, true) => rng.gen_weighted_bool(2),
+    };
+
+    let (first, second) = if split_vertically {
+        let at = rng.gen_range(area.x1 + BSP_MIN_LEAF_SIZE, area.x2 - BSP_MIN_LEAF_SIZE + 1);
+        (Rect { x2: at, ..area }, Rect { x1: at, ..area })
+    } else {
+        let at = rng.gen_range(area.y1 + BSP_MIN_LEAF_SIZE, area.y2 - BSP_MIN_LEAF_SIZE + 1);
+        (Rect { y2: at, ..area }, Rect { y1: at, ..area })
+    };
+
+    let mut rooms = bsp_split(first, map, rng);
+    let second_rooms = bsp_split(second, map, rng);
+
+    // Join the halves through the rooms nearest the cut
+    let (x1, y1) = rooms[rooms.len() - 1].center();
+    let (x2, y2) = second_rooms[0].center();
+    connect_rooms(x1, y1, x2, y2, map, rng);
+
+    rooms.extend(second_rooms);
+    rooms
+}
+
+
+//-----------------------------//
+// CAVES                       //
+//-----------------------------//
+/// Cellular automata caves: random noise smoothed into open, winding caverns
+pub struct Caves;
+
+impl MapGenerator for Caves {
+    fn generate(&self, level: u32, rng: &mut GameRng) -> GeneratedMap {
+        for _ in 0..CAVE_ATTEMPTS {
+            let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+
+            // Random fill, leaving the border solid
+            for x in 1..MAP_WIDTH - 1 {
+                for y in 1..MAP_HEIGHT - 1 {
+                    if rng.gen_range(0, 100) >= CAVE_WALL_CHANCE {
+                        map[x as usize][y as usize] = Tile::empty();
+                    }
+                }
+            }
+
+            // A tile becomes wall when most of its neighbourhood is wall, which clumps the noise together
+            for _ in 0..CAVE_SMOOTHING_STEPS {
+                let previous = map.clone();
+                for x in 1..MAP_WIDTH - 1 {
+                    for y in 1..MAP_HEIGHT - 1 {
+                        let walls = (-1..=1)
+                            .flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+                            .filter(|&(nx, ny)| previous[nx as usize][ny as usize].blocked)
+                            .count();
+                        map[x as usize][y as usize] = if walls >= 5 { Tile::wall() } else { Tile::empty() };
+                    }
+                }
+            }
+
+            // Only the largest cave is kept, the rest could never be reached.
+            // Noise now and then leaves only scraps, in which case start over
+            if keep_largest_area(&mut map) >= (MAP_WIDTH * MAP_HEIGHT / 3) as usize {
+                return open_map_layout(map, rng);
+            }
+        }
+
+        // Bad luck every time, settle for rooms rather than trying forever
+        RoomsAndCorridors.generate(level, rng)
+    }
+}
+
+
+//-----------------------------//
+// DRUNKARD'S WALK             //
+//-----------------------------//
+/// Walkers stumble around at random digging as they go, until enough of the map is open.
+/// Each starts somewhere already dug, so the result is always connected
+pub struct DrunkardsWalk;
+
+impl MapGenerator for DrunkardsWalk {
+    fn generate(&self, _level: u32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+        let target = ((MAP_WIDTH - 2) * (MAP_HEIGHT - 2) * DRUNKARD_FLOOR_PERCENT / 100) as usize;
+        let mut dug = vec![(MAP_WIDTH / 2, MAP_HEIGHT / 2)];
+        map[(MAP_WIDTH / 2) as usize][(MAP_HEIGHT / 2) as usize] = Tile::empty();
+
+        while dug.len() < target {
+            let (mut x, mut y) = dug[rng.gen_range(0, dug.len())];
+            for _ in 0..DRUNKARD_STEPS {
+                let (dx, dy) = [(1, 0), (-1, 0), (0, 1), (0, -1)][rng.gen_range(0, 4)];
+                x = (x + dx).clamp(1, MAP_WIDTH - 2);
+                y = (y + dy).clamp(1, MAP_HEIGHT - 2);
+
+                if map[x as usize][y as usize].blocked {
+                    map[x as usize][y as usize] = Tile::empty();
+                    dug.push((x, y));
+                }
+            }
+        }
+
+        open_map_layout(map, rng)
+    }
+}
+
+
+//-----------------------------//
+// OPEN MAPS                   //
+//-----------------------------//
+/// Stairs and spawn regions for maps without rooms. The player starts on a random floor tile,
+/// the way down is the floor tile furthest from there on foot, and the map is split into squares to spawn in
+fn open_map_layout(map: Map, rng: &mut GameRng) -> GeneratedMap {
+    let floor: Vec<(i32, i32)> = (0..MAP_WIDTH)
+        .flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+        .filter(|&(x, y)| map[x as usize][y as usize].is_walkable())
+        .collect();
+    let up_stairs = floor[rng.gen_range(0, floor.len())];
+
+    let distances = walking_distances(&map, up_stairs);
+    let down_stairs = floor
+        .iter()
+        .copied()
+        .max_by_key(|&(x, y)| distances[x as usize][y as usize])
+        .unwrap_or(up_stairs);
+
+    // Only squares with a fair amount of floor are worth spawning in
+    let mut spawn_regions = vec![];
+    for x in (0..MAP_WIDTH - 1).step_by(SPAWN_REGION_SIZE as usize) {
+        for y in (0..MAP_HEIGHT - 1).step_by(SPAWN_REGION_SIZE as usize) {
+            let region = Rect {
+                x1: x,
+                y1: y,
+                x2: cmp::min(x + SPAWN_REGION_SIZE, MAP_WIDTH - 1),
+                y2: cmp::min(y + SPAWN_REGION_SIZE, MAP_HEIGHT - 1),
+            };
+            let open_tiles = floor
+                .iter()
+                .filter(|&&(fx, fy)| fx > region.x1 && fx < region.x2 && fy > region.y1 && fy < region.y2)
+                .count();
+            if open_tiles as i32 * 4 >= (region.x2 - region.x1 - 1) * (region.y2 - region.y1 - 1) {
+                spawn_regions.push(region);
+            }
+        }
+    }
+
+    GeneratedMap { map, spawn_regions, up_stairs, down_stairs, rooms_wanted: 0, rooms_placed: 0 }
+}
+
+/// Steps needed to walk from `start` to every tile, `None` where it can't be reached
+fn walking_distances(map: &Map, start: (i32, i32)) -> Vec<Vec<Option<u32>>> {
+    let mut distances = vec![vec![None; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    distances[start.0 as usize][start.1 as usize] = Some(0);
+
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y)) = queue.pop_front() {
+        let distance = distances[x as usize][y as usize].unwrap_or(0);
+        for (nx, ny) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if nx < 0 || ny < 0 || nx >= MAP_WIDTH || ny >= MAP_HEIGHT {
+                continue;
+            }
+            if distances[nx as usize][ny as usize].is_none() && map[nx as usize][ny as usize].is_walkable() {
+                distances[nx as usize][ny as usize] = Some(distance + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    distances
+}
+
+/// Fill in every area of floor except the largest. Returns the size of the area kept
+fn keep_largest_area(map: &mut Map) -> usize {
+    let mut seen = vec![vec![false; MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut largest: Option<(usize, Vec<Vec<Option<u32>>>)> = None;
+
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if seen[x as usize][y as usize] || !map[x as usize][y as usize].is_walkable() {
+                continue;
+            }
+
+            let distances = walking_distances(map, (x, y));
+            let mut size = 0;
+            for (column, seen_column) in distances.iter().zip(seen.iter_mut()) {
+                for (distance, seen) in column.iter().zip(seen_column.iter_mut()) {
+                    if distance.is_some() {
+                        *seen = true;
+                        size += 1;
+                    }
+                }
+            }
+            if largest.as_ref().is_none_or(|(largest_size, _)| size > *largest_size) {
+                largest = Some((size, distances));
+            }
+        }
+    }
+
+    let Some((size, reachable)) = largest else {
+        return 0;
+    };
+    for (column, reachable_column) in map.iter_mut().zip(reachable) {
+        for (tile, distance) in column.iter_mut().zip(reachable_column) {
+            if distance.is_none() {
+                *tile = Tile::wall();
+            }
+        }
+    }
+    size
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_crowded_map_reports_the_rooms_it_could_not_fit() {
+        // Far more rooms than the map has space for, so placement has to run out of attempts
+        let mut rng = GameRng::new(7);
+        let generated = place_rooms(1000, &mut rng);
+
+        assert_eq!(generated.rooms_wanted, 1000);
+        assert!(generated.rooms_placed >= 1 && generated.rooms_placed < 1000);
+        assert_eq!(generated.spawn_regions.len(), generated.rooms_placed);
+    }
+}
@@ -1,19 +1,17 @@
-use crate::*;
-
-pub fn move_by(id: usize, dx: i32, dy: i32, map: &Map, objects: &mut [Object]) {
-    let (x, y) = objects[id].pos();
-    if !is_blocked(x + dx, y + dy, map, objects) {
-        objects[id].set_pos(x + dx, y + dy);
-    }
-}
-
-pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
-    // First test map tile
-    if map[x as usize][y as usize].blocked {
-        return true;
-    }
-    // Now check for any blocking objects
-    objects
-        .iter()
-        .any(|object| object.blocks && object.pos() == (x, y))
+use crate::*;
+
+pub fn move_by(id: ObjectId, dx: i32, dy: i32, map: &Map, objects: &mut Objects) {
+    let (x, y) = objects.pos(id);
+    if !is_blocked(x + dx, y + dy, map, objects) {
+        objects.set_pos(id, x + dx, y + dy);
+    }
+}
+
+pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &Objects) -> bool {
+    // First test map tile
+    if map[x as usize][y as usize].blocked {
+        return true;
+    }
+    // Now check for any blocking objects
+    objects.blocker_at(x, y).is_some()
 }
\ No newline at end of file
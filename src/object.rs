@@ -1,201 +1,226 @@
-use rand::{distributions::{IndependentSample, Weighted, WeightedChoice}, Rng};
-use tcod::{colors::*, console::*};
-
-use crate::*;
-
-
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub struct Object {
-    pub x: i32,      
-    pub y: i32,         
-    pub char: char,     
-    pub colour: SerializableColour,  
-    pub name: String,
-    pub blocks: bool,
-    pub alive: bool,
-    pub fighter: Option<Fighter>,
-    pub ai: Option<Ai>,
-    pub item: Option<Item>,
-    pub always_visible: bool,
-    pub level: u32,
-    pub equipment: Option<Equipment>,
-}
-
-impl Object {
-    pub fn new(x: i32, y: i32, char: char, colour: SerializableColour, name: &str, blocks: bool) -> Self {
-        Object { 
-            x, 
-            y, 
-            char, 
-            colour, 
-            name: name.into(), 
-            blocks, 
-            alive: false, 
-            fighter: None, 
-            ai: None, 
-            item: None, 
-            always_visible: false, 
-            level: 1,
-            equipment: None, 
-        }
-    }
-
-    pub fn max_hp(&self, game: &Game) -> i32 {
-        let base_max_hp = self.fighter.map_or(0, |f| f.base_max_hp);
-        let bonus: i32 = self
-            .get_all_equipped(game)
-            .iter()
-            .map(|e| e.max_hp_bonus)
-            .sum();
-        base_max_hp + bonus
-    }
-
-    pub fn pos(&self) -> (i32, i32) {
-        (self.x, self.y)
-    }
-
-    pub fn set_pos(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
-    }
-
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.colour.into());
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
-    }
-
-    pub fn distance(&self, x: i32, y: i32) -> f32 {
-        (((x - self.x).pow(2) + (y - self.y).pow(2)) as f32).sqrt()
-    }
-
-    pub fn distance_to(&self, other: &Object) -> f32 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
-    }
-}
-
-
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
-pub struct Fighter {
-    pub base_max_hp: i32,
-    pub hp: i32,
-    pub base_defense: i32,
-    pub base_power: i32,
-    pub xp: i32,
-    pub on_death: DeathCallback,
-}
-
-
-pub fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>, level: u32) {
-    let max_monsters = from_dungeon_level(&[
-        Transition { level: 1, value: 2 },
-        Transition { level: 4, value: 3 },
-        Transition { level: 6, value: 5 },
-    ], level);
-    
-    let num_monsters = rand::thread_rng().gen_range(0, max_monsters + 1);
-
-    let troll_chance = from_dungeon_level(&[
-        Transition { level: 3, value: 15 },
-        Transition { level: 5, value: 30 },
-        Transition { level: 7, value: 60 },
-    ], level);
-
-    let mut monster_choices = vec![
-        Weighted { weight: 80, item: "orc" },
-        Weighted { weight: troll_chance, item: "troll" },
-    ];
-    let monster_choice = WeightedChoice::new(&mut monster_choices);
-    
-    for _ in 0..num_monsters {
-        if let Some((x, y)) = find_unblocked_position(&room, map, objects) {
-            let monster = create_monster(x, y, monster_choice.ind_sample(&mut rand::thread_rng()));
-            objects.push(monster);
-        }
-    }
-
-    let max_items = from_dungeon_level(&[
-        Transition { level: 1, value: 1 },
-        Transition { level: 4, value: 2 },
-    ], level);
-    
-    let mut item_choices = vec![
-        Weighted { weight: 35, item: Item::Heal },
-        Weighted { weight: from_dungeon_level(&[Transition { level: 4, value: 25 }], level), item: Item::Lightning },
-        Weighted { weight: from_dungeon_level(&[Transition { level: 6, value: 25 }], level), item: Item::Fireball },
-        Weighted { weight: from_dungeon_level(&[Transition { level: 2, value: 10 }], level), item: Item::Confuse },
-        Weighted { weight: from_dungeon_level(&[Transition { level: 4, value:  5 }], level), item: Item::Sword },
-        Weighted { weight: from_dungeon_level(&[Transition { level: 8, value: 15 }], level), item: Item::Shield },
-    ];
-    let item_choice = WeightedChoice::new(&mut item_choices);
-    let num_items = rand::thread_rng().gen_range(0, max_items + 1);
-
-    for _ in 0..num_items {
-        if let Some((x, y)) = find_unblocked_position(&room, map, objects) {
-            let item = create_item(x, y, item_choice.ind_sample(&mut rand::thread_rng()));
-            objects.push(item);
-        }
-    }
-}
-
-fn find_unblocked_position(room: &Rect, map: &Map, objects: &Vec<Object>) -> Option<(i32, i32)> {
-    let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-    let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
-    if !is_blocked(x, y, map, objects) {
-        Some((x, y))
-    } else {
-        None
-    }
-}
-
-fn create_monster(x: i32, y: i32, monster_type: &str) -> Object {
-    let (char, colour, name, fighter) = match monster_type {
-        "orc" => ('o', DESATURATED_GREEN.into(), "orc", Fighter { base_max_hp: 20, hp: 20, base_defense: 0, base_power: 4, xp: 35, on_death: DeathCallback::Monster }),
-        "troll" => ('T', DARKER_GREEN.into(), "troll", Fighter { base_max_hp: 30, hp: 30, base_defense: 2, base_power: 8, xp: 100, on_death: DeathCallback::Monster }),
-        _ => unreachable!(),
-    };
-    let mut monster = Object::new(x, y, char, colour, name, true);
-    monster.fighter = Some(fighter);
-    monster.ai = Some(Ai::Basic);
-    monster.alive = true;
-    monster
-}
-
-fn create_item(x: i32, y: i32, item_type: Item) -> Object {
-    let (char, colour, name) = match item_type {
-        Item::Heal => ('!', VIOLET.into(), "Healing Potion"),
-        Item::Lightning => ('#', LIGHT_YELLOW.into(), "Scroll of Lightning bolt"),
-        Item::Fireball => ('#', DARKER_ORANGE.into(), "Scroll of Fireball"),
-        Item::Confuse => ('#', PURPLE.into(), "Scroll of Confusion"),
-        Item::Sword => ('/', SKY.into(), "Sword"),
-        Item::Shield => ('[', LIGHTER_LIME.into(), "Shield"),
-    };
-
-    let mut object = Object::new(x, y, char, colour, name, false);
-    object.item = Some(item_type);
-
-    match item_type {
-        Item::Sword => {
-            object.equipment = Some(Equipment {
-                equipped: false,
-                slot: Slot::RightHand,
-                power_bonus: 3, // Adjust as needed
-                defense_bonus: 0,
-                max_hp_bonus: 0,
-            });
-        }
-        Item::Shield => {
-            object.equipment = Some(Equipment {
-                equipped: false,
-                slot: Slot::LeftHand,
-                power_bonus: 0,
-                defense_bonus: 2, // Adjust as needed
-                max_hp_bonus: 0,
-            });
-        }
-        _ => {}
-    }
-
-    object
-}
+use rand::{distributions::{IndependentSample, Weighted, WeightedChoice}, Rng};
+
+use crate::*;
+
+
+/// How an object looks on the map
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Renderable {
+    pub char: char,
+    pub colour: SerializableColour,
+    pub always_visible: bool,   // Still drawn out of sight, once its tile has been explored
+}
+
+impl Renderable {
+    pub fn new(char: char, colour: SerializableColour) -> Self {
+        Renderable { char, colour, always_visible: false }
+    }
+}
+
+/// Marks an object that nothing else can share a tile with
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Blocks;
+
+/// Marks the object the player controls
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Player;
+
+impl Objects {
+    /// Where an object on the current floor is
+    pub fn pos(&self, id: ObjectId) -> (i32, i32) {
+        self.positions[id]
+    }
+
+    pub fn set_pos(&mut self, id: ObjectId, x: i32, y: i32) {
+        self.positions.insert(id, (x, y));
+    }
+
+    pub fn distance(&self, id: ObjectId, x: i32, y: i32) -> f32 {
+        let (own_x, own_y) = self.pos(id);
+        (((x - own_x).pow(2) + (y - own_y).pow(2)) as f32).sqrt()
+    }
+
+    pub fn distance_to(&self, id: ObjectId, other: ObjectId) -> f32 {
+        let (x, y) = self.pos(other);
+        self.distance(id, x, y)
+    }
+
+    /// Everything on the current floor at (x, y)
+    pub fn at(&self, x: i32, y: i32) -> impl Iterator<Item = ObjectId> + '_ {
+        self.positions.iter().filter(move |&(_, &pos)| pos == (x, y)).map(|(id, _)| id)
+    }
+
+    /// Whatever stands in the way at (x, y), if anything does
+    pub fn blocker_at(&self, x: i32, y: i32) -> Option<ObjectId> {
+        self.at(x, y).find(|&id| self.blockers.contains(id))
+    }
+
+    /// Whether an object can still fight. The dead lose their fighter, except the player
+    pub fn is_alive(&self, id: ObjectId) -> bool {
+        self.fighters.get(id).is_some_and(|f| f.hp > 0)
+    }
+
+    /// The name, with how many there are for a stack
+    pub fn display_name(&self, id: ObjectId) -> String {
+        let name = match (self.unidentified.get(id), self.enchantments.get(id)) {
+            (Some(appearance), _) => appearance.clone(),
+            (None, Some(enchantment)) => enchantment.full_name(&self.names[id]),
+            (None, None) => self.names[id].clone(),
+        };
+        match self.counts.get(id) {
+            Some(&count) if count > 1 => format!("{} ({})", name, count),
+            _ => name,
+        }
+    }
+
+    /// Whether item `id` can share an inventory slot with `other`
+    pub fn stacks_with(&self, id: ObjectId, other: ObjectId) -> bool {
+        self.items.get(id).is_some_and(|item| item.stacks())
+            && self.names[id] == self.names[other]
+            && self.ranged.get(id) == self.ranged.get(other)
+            && self.enchantments.get(id) == self.enchantments.get(other)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Fighter {
+    pub base_max_hp: i32,
+    pub hp: i32,
+    pub base_defense: i32,     // Armour, taken off the damage of every hit
+    pub base_evasion: i32,     // Makes attacks miss more often
+    pub base_power: i32,
+    pub xp: i32,
+    pub on_death: DeathCallback,
+    pub on_hit: Option<StatusEffect>,   // Put on whatever this hurts
+    pub speed: i32,     // Energy gained each tick
+    pub energy: i32,    // Can act when 0 or more
+    pub level: u32,     // Only goes up for the player
+}
+
+
+pub fn place_objects(room: Rect, map: &Map, objects: &mut Objects, level: u32, rng: &mut GameRng) {
+    let spawn_tables = &content().spawn_tables;
+    let max_monsters = from_dungeon_level(&spawn_tables.monsters_per_room, level);
+    let num_monsters = rng.gen_range(0, max_monsters + 1);
+
+    let mut monster_choices: Vec<_> = content()
+        .monsters
+        .iter()
+        .map(|monster| Weighted { weight: from_dungeon_level(&monster.spawn_weight, level), item: monster })
+        .collect();
+    let monster_choice = WeightedChoice::new(&mut monster_choices);
+    
+    for _ in 0..num_monsters {
+        if let Some((x, y)) = find_unblocked_position(&room, map, objects, rng) {
+            let def = monster_choice.ind_sample(rng);
+            let monster = create_monster(x, y, def, objects);
+
+            // Some are found asleep, and can be sneaked past
+            if objects.ais[monster] == Ai::Basic && rng.gen_range(0, 100) < SLEEP_CHANCE {
+                objects.ais[monster] = Ai::Asleep;
+            }
+            give_gear(monster, def, level, objects, rng);
+        }
+    }
+
+    let max_items = from_dungeon_level(&spawn_tables.items_per_room, level);
+
+    let mut item_choices: Vec<_> = content()
+        .items
+        .iter()
+        .map(|item| Weighted { weight: from_dungeon_level(&item.spawn_weight, level), item })
+        .collect();
+    let item_choice = WeightedChoice::new(&mut item_choices);
+    let num_items = rng.gen_range(0, max_items + 1);
+
+    for _ in 0..num_items {
+        if let Some((x, y)) = find_unblocked_position(&room, map, objects, rng) {
+            let item = create_item(item_choice.ind_sample(rng), objects);
+            enchant(item, level, rng, objects);
+            objects.set_pos(item, x, y);
+        }
+    }
+}
+
+fn find_unblocked_position(room: &Rect, map: &Map, objects: &Objects, rng: &mut GameRng) -> Option<(i32, i32)> {
+    let x = rng.gen_range(room.x1 + 1, room.x2);
+    let y = rng.gen_range(room.y1 + 1, room.y2);
+    if !is_blocked(x, y, map, objects) {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+fn create_monster(x: i32, y: i32, monster: &MonsterDef, objects: &mut Objects) -> ObjectId {
+    let id = objects.spawn(&monster.name);
+    objects.set_pos(id, x, y);
+    objects.renderables.insert(id, Renderable::new(monster.glyph, monster.colour));
+    objects.blockers.insert(id, Blocks);
+    objects.fighters.insert(id, Fighter {
+        base_max_hp: monster.max_hp,
+        hp: monster.max_hp,
+        base_defense: monster.defense,
+        base_evasion: monster.evasion,
+        base_power: monster.power,
+        xp: monster.xp,
+        on_death: DeathCallback::Monster,
+        on_hit: monster.on_hit,
+        speed: monster.speed,
+        energy: 0,
+        level: 1,
+    });
+    objects.statuses.insert(id, vec![]);
+    objects.ais.insert(id, match monster.ai {
+        AiKind::Basic => Ai::Basic,
+        AiKind::Sleeper => Ai::Asleep,
+        AiKind::Wanderer => Ai::Wandering { destination: (x, y) },
+    });
+    objects.senses.insert(id, monster.senses);
+    objects.inventories.insert(id, vec![]);
+    if let Some(attack) = monster.ranged_attack {
+        objects.ranged.insert(id, Ranged::Attack { damage: attack.damage, range: attack.range });
+    }
+    id
+}
+
+/// Roll for each piece of gear the monster's kind may carry, wielding what it can
+fn give_gear(monster: ObjectId, def: &MonsterDef, level: u32, objects: &mut Objects, rng: &mut GameRng) {
+    for gear in &def.gear {
+        if rng.gen_range(0, 100) < gear.chance {
+            // Content validation makes sure the item exists
+            let item_def = content().items.iter().find(|item| item.name == gear.item).unwrap();
+            let item = create_item(item_def, objects);
+            enchant(item, level, rng, objects);
+            objects.inventories[monster].push(item);
+            if fits_free_slot(item, monster, objects) {
+                objects.equipment[item].equipped = true;
+            }
+        }
+    }
+}
+
+/// A new item, not yet anywhere: place it on the map or put it in an inventory
+pub fn create_item(item: &ItemDef, objects: &mut Objects) -> ObjectId {
+    let id = objects.spawn(&item.name);
+    objects.renderables.insert(id, Renderable::new(item.glyph, item.colour));
+    objects.items.insert(id, item.effect);
+    objects.counts.insert(id, item.count);
+    if let Some(equipment) = item.equipment {
+        objects.equipment.insert(id, Equipment {
+            slot: equipment.slot,
+            equipped: false,
+            max_hp_bonus: equipment.max_hp_bonus,
+            power_bonus: equipment.power_bonus,
+            defense_bonus: equipment.defense_bonus,
+            evasion_bonus: equipment.evasion_bonus,
+            damage: equipment.damage,
+            two_handed: equipment.two_handed,
+        });
+    }
+    if let Some(ranged) = &item.ranged {
+        objects.ranged.insert(id, ranged.clone());
+    }
+    id
+}
@@ -0,0 +1,65 @@
+use std::cmp::{self, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::*;
+
+/// Shortest path from `start` to `goal` with A*, moving in all eight directions like everything else.
+/// Walls block the way. Blocking objects are only soft obstacles that cost extra to go through,
+/// as they may well have moved on by the time they are reached.
+/// Returns the tiles to step on, `goal` included, or `None` if walls cut it off
+pub fn find_path(start: (i32, i32), goal: (i32, i32), map: &Map, objects: &Objects) -> Option<Vec<(i32, i32)>> {
+    let index = |(x, y): (i32, i32)| (x * MAP_HEIGHT + y) as usize;
+    let tile_count = (MAP_WIDTH * MAP_HEIGHT) as usize;
+
+    let mut crowded = vec![false; tile_count];
+    for (_, &pos) in objects.positions.iter().filter(|&(id, _)| objects.blockers.contains(id)) {
+        crowded[index(pos)] = true;
+    }
+
+    let mut cost = vec![u32::MAX; tile_count];
+    let mut came_from = vec![None; tile_count];
+    let mut open = BinaryHeap::new();
+    cost[index(start)] = 0;
+    open.push(Reverse((distance_estimate(start, goal), start)));
+
+    while let Some(Reverse((_, current))) = open.pop() {
+        if current == goal {
+            // Walk back from the goal to get the path
+            let mut path = vec![];
+            let mut step = goal;
+            while step != start {
+                path.push(step);
+                step = came_from[index(step)].unwrap();
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = (current.0 + dx, current.1 + dy);
+                if (dx, dy) == (0, 0) || next.0 < 0 || next.1 < 0 || next.0 >= MAP_WIDTH || next.1 >= MAP_HEIGHT {
+                    continue;
+                }
+                if map[next.0 as usize][next.1 as usize].blocked {
+                    continue;
+                }
+
+                // Whoever is on the goal is the one being chased, so they don't count as in the way
+                let crowd_cost = if crowded[index(next)] && next != goal { CROWDED_STEP_COST } else { 0 };
+                let next_cost = cost[index(current)] + 1 + crowd_cost;
+                if next_cost < cost[index(next)] {
+                    cost[index(next)] = next_cost;
+                    came_from[index(next)] = Some(current);
+                    open.push(Reverse((next_cost + distance_estimate(next, goal), next)));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Fewest steps between two tiles with nothing in the way, with diagonal steps allowed
+fn distance_estimate(from: (i32, i32), to: (i32, i32)) -> u32 {
+    cmp::max((from.0 - to.0).abs(), (from.1 - to.1).abs()) as u32
+}
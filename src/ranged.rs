@@ -0,0 +1,148 @@
+use crate::*;
+
+const THROW_RANGE: i32 = 4;     // How far things not made for throwing can be thrown
+
+
+/// What something can do at a distance
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum Ranged {
+    /// Shoots ammunition of the same kind while equipped, adding its power to the damage
+    Launcher {
+        ammo: String,
+        power: i32,
+        range: i32,
+    },
+    /// Shot from a launcher of the same kind
+    Ammo {
+        kind: String,
+        damage: Dice,
+    },
+    /// Made to be thrown by hand
+    Thrown {
+        damage: Dice,
+        range: i32,
+    },
+    /// A monster's own ranged attack, which never runs out
+    Attack {
+        damage: Dice,
+        range: i32,
+    },
+}
+
+/// Where a missile ends up
+pub enum Flight {
+    /// Struck this object, and drops at its feet
+    Hit(ObjectId),
+    /// Missed everything and came down on this tile
+    Landed((i32, i32)),
+}
+
+/// Follow a missile from `from` towards `to`, at most `range` tiles away. It hits the first blocking
+/// object in the way and stops short of walls. With nothing in the way it comes down on `to`
+pub fn line_of_flight(from: (i32, i32), to: (i32, i32), range: i32, map: &Map, objects: &Objects) -> Flight {
+    let mut landing = from;
+    for (x, y) in line(from.0, from.1, to.0, to.1).skip(1) {
+        let distance = (((x - from.0).pow(2) + (y - from.1).pow(2)) as f32).sqrt();
+        if map[x as usize][y as usize].blocked || distance > range as f32 {
+            break;
+        }
+        if let Some(id) = objects.blocker_at(x, y) {
+            return Flight::Hit(id);
+        }
+        landing = (x, y);
+    }
+    Flight::Landed(landing)
+}
+
+/// Shoot the equipped launcher at a tile of the player's choice, using up one of its ammunition.
+/// Returns whether anything was shot
+pub fn fire(frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> bool {
+    let inventory = &objects.inventories[PLAYER];
+    let launcher = inventory.iter().find_map(|&item_id| match (objects.ranged.get(item_id), objects.equipment.get(item_id)) {
+        (Some(Ranged::Launcher { ammo, power, range }), Some(equipment)) if equipment.equipped => {
+            Some((objects.names[item_id].clone(), ammo.clone(), *power, *range))
+        }
+        _ => None,
+    });
+    let Some((launcher, ammo, power, range)) = launcher else {
+        game.messages.add("You have nothing equipped to shoot with.", RED);
+        return false;
+    };
+
+    let ammo = inventory.iter().find_map(|&item_id| match objects.ranged.get(item_id) {
+        Some(Ranged::Ammo { kind, damage }) if *kind == ammo => Some((item_id, *damage)),
+        _ => None,
+    });
+    let Some((ammo_id, damage)) = ammo else {
+        game.messages.add(format!("You have nothing to shoot from your {}.", launcher), RED);
+        return false;
+    };
+
+    game.messages.add("Left-click on a target tile to shoot at, or Right-click to cancel.", LIGHT_CYAN);
+    let Some(target) = target_tile(frontend, game, objects, Some(range as f32)) else {
+        game.messages.add("Cancelled", WHITE);
+        return false;
+    };
+
+    let missile = take_one(ammo_id, objects);
+    launch(missile, Some(damage.plus(power)), target, range, game, objects);
+    true
+}
+
+/// Throw something from the inventory at a tile of the player's choice.
+/// Anything can be thrown, but only what is made for it does any harm. Returns whether anything was thrown
+pub fn throw(frontend: &mut dyn Frontend, game: &mut Game, objects: &mut Objects) -> bool {
+    let Some(inventory_index) = frontend.choose_inventory_item(objects, "Press the key next to an item to throw it, or any other to cancel.\n") else {
+        return false;
+    };
+    let item_id = objects.inventories[PLAYER][inventory_index];
+    let (damage, range) = match objects.ranged.get(item_id) {
+        Some(&Ranged::Thrown { damage, range }) => (Some(damage), range),
+        _ => (None, THROW_RANGE),
+    };
+
+    game.messages.add("Left-click on a target tile to throw at, or Right-click to cancel.", LIGHT_CYAN);
+    let Some(target) = target_tile(frontend, game, objects, Some(range as f32)) else {
+        game.messages.add("Cancelled", WHITE);
+        return false;
+    };
+
+    let missile = take_one(item_id, objects);
+    if objects.equipment.contains(missile) { unequip(missile, objects, &mut game.messages); }
+    launch(missile, damage, target, range, game, objects);
+    true
+}
+
+/// Take a single item out of the player's inventory, splitting it off if it's a stack
+fn take_one(item_id: ObjectId, objects: &mut Objects) -> ObjectId {
+    if objects.counts.get(item_id).is_some_and(|&count| count > 1) {
+        split_stack(item_id, 1, objects)
+    } else {
+        objects.inventories[PLAYER].retain(|&id| id != item_id);
+        item_id
+    }
+}
+
+/// Send a missile from the player towards `target`. Whatever it hits is shot for `damage`, or has it
+/// bounce off if it isn't made to hurt, and the missile drops to the floor wherever it ends up
+fn launch(missile: ObjectId, damage: Option<Dice>, target: (i32, i32), range: i32, game: &mut Game, objects: &mut Objects) {
+    game.noise = SHOOT_NOISE;
+    let player_pos = objects.pos(PLAYER);
+    let (x, y) = match line_of_flight(player_pos, target, range, &game.map, objects) {
+        Flight::Hit(target_id) => {
+            if let Some(damage) = damage {
+                shoot(PLAYER, target_id, damage, game, objects);
+            } else {
+                game.messages.add(format!("The {} bounces off the {}.", objects.names[missile], objects.names[target_id]), WHITE);
+            }
+            alert(target_id, player_pos, objects);
+            objects.pos(target_id)
+        }
+        Flight::Landed(landing) => {
+            game.messages.add(format!("The {} hits nothing.", objects.names[missile]), WHITE);
+            landing
+        }
+    };
+    objects.set_pos(missile, x, y);
+}
@@ -0,0 +1,141 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use crate::*;
+
+pub const REPLAY_FILE: &str = "replay";
+
+/// How a recorded session started, so the same state can be rebuilt before replaying it
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum ReplayStart {
+    /// A fresh game, fully determined by its seed
+    NewGame { seed: u64 },
+    /// A loaded game, stored as it was when the session started (RNG state included)
+    Continue { snapshot: serde_json::Value },
+}
+
+impl ReplayStart {
+    pub fn snapshot(game: &Game, objects: &Objects) -> Result<Self, Box<dyn Error>> {
+        Ok(ReplayStart::Continue { snapshot: serde_json::to_value((game, objects))? })
+    }
+
+    /// Rebuild the game state the session started from
+    pub fn restore(&self) -> Result<(Game, Objects), Box<dyn Error>> {
+        match self {
+            ReplayStart::NewGame { seed } => Ok(new_game(*seed)),
+            ReplayStart::Continue { snapshot } => {
+                let (mut game, objects) = serde_json::from_value::<(Game, Objects)>(snapshot.clone())?;
+                initialise_fov(&mut game);
+                Ok((game, objects))
+            }
+        }
+    }
+}
+
+/// A recorded session: the starting state and every input given to the game, in order
+pub struct Replay {
+    pub start: ReplayStart,
+    pub inputs: Vec<Input>,
+}
+
+impl Replay {
+    /// Read a replay file: one JSON header line with the `ReplayStart`, then one `Input` per line
+    pub fn load(path: &str) -> Result<Replay, Box<dyn Error>> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let start = match lines.next() {
+            Some(header) => serde_json::from_str(&header?)?,
+            None => return Err("Replay file is empty".into()),
+        };
+
+        let mut inputs = vec![];
+        for line in lines {
+            // A crash can leave the last line half written, the run is good up to there
+            match serde_json::from_str(&line?) {
+                Ok(input) => inputs.push(input),
+                Err(_) => break,
+            }
+        }
+
+        Ok(Replay { start, inputs })
+    }
+}
+
+
+/// Frontend wrapper writing every input to a replay file as it is given.
+/// Each input is written straight away, so the replay survives a crash
+pub struct RecordingFrontend<'a> {
+    inner: &'a mut dyn Frontend,
+    file: File,
+}
+
+impl<'a> RecordingFrontend<'a> {
+    pub fn create(path: &str, inner: &'a mut dyn Frontend, start: &ReplayStart) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", serde_json::to_string(start)?)?;
+        Ok(RecordingFrontend { inner, file })
+    }
+
+    fn record(&mut self, input: Input) {
+        // Losing the recording is no reason to interrupt the game
+        if let Ok(line) = serde_json::to_string(&input) {
+            let _ = writeln!(self.file, "{}", line);
+        }
+    }
+}
+
+impl Renderer for RecordingFrontend<'_> {
+    fn render(&mut self, game: &Game, objects: &Objects) {
+        self.inner.render(game, objects);
+    }
+
+    fn msgbox(&mut self, text: &str, width: i32) {
+        self.inner.msgbox(text, width);
+    }
+}
+
+impl InputSource for RecordingFrontend<'_> {
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn next_command(&mut self, game: &Game, objects: &Objects) -> Option<Command> {
+        let command = self.inner.next_command(game, objects);
+        if let Some(command) = command {
+            self.record(Input::Command(command));
+        }
+        command
+    }
+
+    fn choose_inventory_item(&mut self, objects: &Objects, header: &str) -> Option<usize> {
+        let choice = self.inner.choose_inventory_item(objects, header);
+        self.record(Input::Inventory(choice));
+        choice
+    }
+
+    fn choose_target_tile(&mut self, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<(i32, i32)> {
+        let choice = self.inner.choose_target_tile(game, objects, max_range);
+        self.record(Input::Target(choice));
+        choice
+    }
+
+    fn choose_quantity(&mut self, header: &str, max: u32) -> Option<u32> {
+        let choice = self.inner.choose_quantity(header, max);
+        self.record(Input::Quantity(choice));
+        choice
+    }
+
+    fn choose_level_up(&mut self, options: &[String]) -> usize {
+        let choice = self.inner.choose_level_up(options);
+        self.record(Input::LevelUp(choice));
+        choice
+    }
+}
+
+/// Play a session, recording it to `REPLAY_FILE` if possible
+pub fn play_recorded(frontend: &mut dyn Frontend, start: ReplayStart, game: &mut Game, objects: &mut Objects) {
+    match RecordingFrontend::create(REPLAY_FILE, frontend, &start) {
+        Ok(mut recorder) => play_game(&mut recorder, game, objects),
+        Err(_) => play_game(frontend, game, objects),
+    }
+}
@@ -0,0 +1,51 @@
+use rand::Rng;
+
+/// Seedable random number generator used for every random decision in a game.
+/// It lives on `Game` and is saved with it, so a run can be reproduced from its seed
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng { seed, state: seed }
+    }
+
+    /// Start from a random seed
+    pub fn from_entropy() -> Self {
+        GameRng::new(random_seed())
+    }
+
+    /// The seed this generator was created with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// Pick a fresh seed for a new run
+pub fn random_seed() -> u64 {
+    rand::thread_rng().next_u64()
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::from_entropy()
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    // SplitMix64: small, fast and with a state that is trivial to serialize
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,846 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::*;
+
+const LEGACY_SAVE_FILE: &str = "savegame";    // Single save from before slots, in the working directory
+const SAVE_EXTENSION: &str = "save";
+const SAVE_MAGIC: &str = "tombs-of-the-fallen";
+const BINARY_MAGIC: &[u8] = b"TOTF-BIN";     // Starts binary saves, before the header line
+const GAME_DIR_NAME: &str = "tombs-of-the-fallen";
+pub const MAX_SLOT_NAME_LENGTH: usize = 20;
+
+/// Bump this whenever `Game`, `Objects` or anything they contain changes shape,
+/// and add the matching step to `migrate`
+pub const SAVE_VERSION: u32 = 19;
+
+
+/// How the body of a save is encoded. Both kinds share the same JSON header line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaveFormat {
+    /// Plain JSON, easy to read and edit by hand
+    Json,
+    /// CBOR with packed maps and colours, much smaller and faster to write
+    Binary,
+}
+
+/// First line of a save file, the body follows on the next line
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SaveHeader {
+    magic: String,
+    version: u32,
+    checksum: u64,
+    #[serde(default)]
+    metadata: Option<SaveMetadata>,
+}
+
+/// Summary of a save, kept in the header so slots can be listed without loading them
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SaveMetadata {
+    pub character_level: u32,
+    pub dungeon_level: u32,
+    pub turns: u32,
+    pub timestamp: u64,     // Seconds since the Unix epoch
+}
+
+/// A named save in the save directory
+pub struct SaveSlot {
+    pub name: String,
+    pub metadata: Option<SaveMetadata>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Missing,
+    Corrupt(String),
+    TooNew(u32),
+    Io(io::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Missing => write!(f, "No saved game to load :("),
+            SaveError::Corrupt(reason) => write!(f, "The saved game is corrupted and can't be loaded ({}).", reason),
+            SaveError::TooNew(version) => write!(f, "The saved game was made by a newer version of the game (save format {}).", version),
+            SaveError::Io(e) => write!(f, "Could not read the saved game: {}", e),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => SaveError::Missing,
+            io::ErrorKind::InvalidData => SaveError::Corrupt(e.to_string()),
+            _ => SaveError::Io(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Corrupt(e.to_string())
+    }
+}
+
+
+pub fn save_game(slot: &str, game: &Game, objects: &Objects, format: SaveFormat) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(save_dir())?;
+    write_save(&slot_path(slot), game, objects, format)
+}
+
+/// Load a save in either format, telling them apart by the binary magic
+pub fn load_game(slot: &str) -> Result<(Game, Objects), SaveError> {
+    read_save(&slot_path(slot))
+}
+
+/// Write the game to a temporary file, then move it over the old save,
+/// so a crash mid-write never leaves a half written save behind
+fn write_save(path: &Path, game: &Game, objects: &Objects, format: SaveFormat) -> Result<(), Box<dyn Error>> {
+    let body = match format {
+        SaveFormat::Json => serde_json::to_vec(&(game, objects))?,
+        SaveFormat::Binary => {
+            let mut body = vec![];
+            ciborium::into_writer(&(game, objects), &mut body)?;
+            body
+        }
+    };
+    let header = SaveHeader {
+        magic: SAVE_MAGIC.into(),
+        version: SAVE_VERSION,
+        checksum: checksum(&body),
+        metadata: Some(SaveMetadata {
+            character_level: objects.fighters[PLAYER].level,
+            dungeon_level: game.dungeon_level,
+            turns: game.turns,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_secs()),
+        }),
+    };
+
+    let temp_path = path.with_extension("tmp");
+    let mut file = File::create(&temp_path)?;
+    if format == SaveFormat::Binary {
+        file.write_all(BINARY_MAGIC)?;
+    }
+    writeln!(file, "{}", serde_json::to_string(&header)?)?;
+    file.write_all(&body)?;
+    file.sync_all()?;
+
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+fn read_save(path: &Path) -> Result<(Game, Objects), SaveError> {
+    let contents = fs::read(path)?;
+    let (format, contents) = match contents.strip_prefix(BINARY_MAGIC) {
+        Some(rest) => (SaveFormat::Binary, rest),
+        None => (SaveFormat::Json, contents.as_slice()),
+    };
+
+    let header = contents
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .and_then(|end| Some((parse_header(&contents[..end])?, &contents[end + 1..])));
+
+    let (version, body) = match header {
+        Some((header, body)) => {
+            if header.version > SAVE_VERSION {
+                return Err(SaveError::TooNew(header.version));
+            }
+            if header.checksum != checksum(body) {
+                return Err(SaveError::Corrupt("Checksum mismatch".into()));
+            }
+            (header.version, body)
+        }
+        // Saves from before the header was added are a bare JSON body
+        None if format == SaveFormat::Json => (0, contents),
+        None => return Err(SaveError::Corrupt("Missing header".into())),
+    };
+
+    let body = match format {
+        SaveFormat::Json => serde_json::from_slice(body)?,
+        SaveFormat::Binary => ciborium::from_reader(body).map_err(|e| SaveError::Corrupt(e.to_string()))?,
+    };
+    let save = migrate(version, body)?;
+    let (mut game, objects) = serde_json::from_value::<(Game, Objects)>(save)?;
+    initialise_fov(&mut game);
+    Ok((game, objects))
+}
+
+
+//-----------------------------//
+// SLOTS                       //
+//-----------------------------//
+/// Per-user directory the save slots live in
+fn save_dir() -> PathBuf {
+    let data_dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+    };
+    // Fall back to the working directory if there's no home to speak of
+    data_dir.unwrap_or_default().join(GAME_DIR_NAME).join("saves")
+}
+
+fn slot_path(slot: &str) -> PathBuf {
+    save_dir().join(slot).with_extension(SAVE_EXTENSION)
+}
+
+/// Slot names end up as file names, so keep them to a safe set of characters
+pub fn is_valid_slot_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_'
+}
+
+pub fn slot_exists(slot: &str) -> bool {
+    slot_path(slot).exists()
+}
+
+/// All save slots, most recently saved first
+pub fn list_slots() -> Vec<SaveSlot> {
+    import_legacy_save();
+
+    let entries = match fs::read_dir(save_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut slots: Vec<SaveSlot> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == SAVE_EXTENSION))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            let metadata = read_header(&path).and_then(|header| header.metadata);
+            Some(SaveSlot { name, metadata })
+        })
+        .collect();
+
+    slots.sort_by_key(|slot| std::cmp::Reverse(slot.metadata.as_ref().map_or(0, |m| m.timestamp)));
+    slots
+}
+
+pub fn delete_slot(slot: &str) -> io::Result<()> {
+    fs::remove_file(slot_path(slot))
+}
+
+pub fn rename_slot(slot: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+    if slot_exists(new_name) {
+        return Err(format!("There is already a save called {}", new_name).into());
+    }
+    fs::rename(slot_path(slot), slot_path(new_name))?;
+    Ok(())
+}
+
+/// Format an existing slot was saved in, so it can be saved the same way again
+pub fn slot_format(slot: &str) -> Option<SaveFormat> {
+    let mut start = [0; BINARY_MAGIC.len()];
+    File::open(slot_path(slot)).ok()?.read_exact(&mut start).ok()?;
+    Some(if start == BINARY_MAGIC { SaveFormat::Binary } else { SaveFormat::Json })
+}
+
+fn read_header(path: &PathBuf) -> Option<SaveHeader> {
+    let mut line = vec![];
+    BufReader::new(File::open(path).ok()?).read_until(b'\n', &mut line).ok()?;
+    parse_header(line.strip_prefix(BINARY_MAGIC).unwrap_or(&line))
+}
+
+fn parse_header(line: &[u8]) -> Option<SaveHeader> {
+    serde_json::from_slice::<SaveHeader>(line).ok().filter(|header| header.magic == SAVE_MAGIC)
+}
+
+/// Move a save from before slots existed into its own slot
+fn import_legacy_save() {
+    let legacy_slot = slot_path(LEGACY_SAVE_FILE);
+    if fs::metadata(LEGACY_SAVE_FILE).is_err() || legacy_slot.exists() {
+        return;
+    }
+    if fs::create_dir_all(save_dir()).is_ok() && fs::copy(LEGACY_SAVE_FILE, &legacy_slot).is_ok() {
+        let _ = fs::remove_file(LEGACY_SAVE_FILE);
+    }
+}
+
+
+//-----------------------------//
+// MIGRATIONS                  //
+//-----------------------------//
+/// Bring a save body from `version` up to `SAVE_VERSION`, one version at a time
+fn migrate(mut version: u32, mut save: Value) -> Result<Value, SaveError> {
+    while version < SAVE_VERSION {
+        save = match version {
+            0 => migrate_v0(save)?,
+            1 => migrate_v1(save)?,
+            2 => migrate_v2(save)?,
+            // Version 4 only added new AI states, older saves load as they are
+            3 => save,
+            4 => migrate_v4(save)?,
+            5 => migrate_v5(save)?,
+            6 => migrate_v6(save)?,
+            7 => migrate_v7(save)?,
+            8 => migrate_v8(save)?,
+            9 => migrate_v9(save)?,
+            10 => migrate_v10(save)?,
+            11 => migrate_v11(save)?,
+            12 => migrate_v12(save)?,
+            13 => migrate_v13(save)?,
+            14 => migrate_v14(save)?,
+            15 => migrate_v15(save)?,
+            16 => migrate_v16(save)?,
+            // Version 18 only recorded who put each status on, older ones load with nobody to credit
+            17 => save,
+            18 => migrate_v18(save)?,
+            _ => unreachable!("Missing migration from save format {}", version),
+        };
+        version += 1;
+    }
+    Ok(save)
+}
+
+/// Version 0 had no header and no RNG. The oldest of those saves also predate
+/// the equipment bonuses, with unprefixed `Fighter` stats and a generic `Equipment` item
+fn migrate_v0(mut save: Value) -> Result<Value, SaveError> {
+    let game = save
+        .get_mut(0)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the game state first".into()))?;
+    game.entry("rng").or_insert(serde_json::to_value(GameRng::from_entropy())?);
+
+    for_each_object(&mut save, |object| {
+        if let Some(fighter) = object.get_mut("fighter").and_then(Value::as_object_mut) {
+            for (old, new) in [("max_hp", "base_max_hp"), ("defense", "base_defense"), ("power", "base_power")] {
+                if let Some(value) = fighter.remove(old) {
+                    fighter.insert(new.into(), value);
+                }
+            }
+        }
+
+        let slot = object.get("equipment").and_then(|e| e.get("slot")).cloned();
+        if object.get("item") == Some(&Value::from("Equipment")) {
+            // Only swords and shields could be found back then
+            let item = if slot == Some(Value::from("LeftHand")) { "Shield" } else { "Sword" };
+            object.insert("item".into(), item.into());
+        }
+
+        let (power, defense) = match object.get("item").and_then(Value::as_str) {
+            Some("Sword") => (3, 0),
+            Some("Shield") => (0, 2),
+            _ => (0, 0),
+        };
+        if let Some(equipment) = object.get_mut("equipment").and_then(Value::as_object_mut) {
+            equipment.entry("max_hp_bonus").or_insert(0.into());
+            equipment.entry("power_bonus").or_insert(power.into());
+            equipment.entry("defense_bonus").or_insert(defense.into());
+        }
+    });
+    Ok(save)
+}
+
+/// Version 1 didn't count turns
+fn migrate_v1(mut save: Value) -> Result<Value, SaveError> {
+    let game = save
+        .get_mut(0)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the game state first".into()))?;
+    game.entry("turns").or_insert(0.into());
+    Ok(save)
+}
+
+/// Version 2 only kept the current floor, whose single "Stairs" went down
+fn migrate_v2(mut save: Value) -> Result<Value, SaveError> {
+    let game = save
+        .get_mut(0)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the game state first".into()))?;
+    game.entry("dungeon").or_insert(serde_json::json!({ "levels": [] }));
+
+    for_each_object(&mut save, |object| {
+        if object.get("name") == Some(&Value::from("Stairs")) {
+            object.insert("name".into(), DOWN_STAIRS.into());
+            object.insert("char".into(), ">".into());
+        }
+    });
+    Ok(save)
+}
+
+/// Version 4 monsters shared the player's FOV, now each has senses of its own
+fn migrate_v4(mut save: Value) -> Result<Value, SaveError> {
+    for_each_object(&mut save, |object| {
+        let is_monster = object.get("ai").is_some_and(|ai| !ai.is_null());
+        let senses = match object.get("name").and_then(Value::as_str) {
+            _ if !is_monster => Value::Null,
+            Some("troll") => serde_json::json!({ "sight_radius": 6, "perception": 30, "hearing": 60 }),
+            _ => serde_json::json!({ "sight_radius": 8, "perception": 50, "hearing": 100 }),
+        };
+        object.entry("senses").or_insert(senses);
+    });
+    Ok(save)
+}
+
+/// Version 5 had an item kind per weapon and armour, they all just equip now
+fn migrate_v5(mut save: Value) -> Result<Value, SaveError> {
+    for_each_object(&mut save, |object| {
+        if matches!(object.get("item").and_then(Value::as_str), Some("Sword" | "Shield")) {
+            object.insert("item".into(), "Equip".into());
+        }
+    });
+    Ok(save)
+}
+
+/// Version 6 had no ranged combat, so nothing could be shot, thrown or stacked
+fn migrate_v6(mut save: Value) -> Result<Value, SaveError> {
+    for_each_object(&mut save, |object| {
+        object.entry("ranged").or_insert(Value::Null);
+        object.entry("count").or_insert(1.into());
+    });
+    Ok(save)
+}
+
+/// Version 7 confused monsters by swapping out their AI, and had no other status effects
+fn migrate_v7(mut save: Value) -> Result<Value, SaveError> {
+    for_each_object(&mut save, |object| {
+        let mut statuses = vec![];
+        while let Some(confused) = object.get_mut("ai").and_then(|ai| ai.get_mut("Confused")).map(Value::take) {
+            // Confusing a confused monster wrapped one AI in another
+            let turns = confused.get("num_turns").and_then(Value::as_i64).unwrap_or(0);
+            if turns > 0 && statuses.is_empty() {
+                statuses.push(serde_json::json!({ "kind": "Confused", "turns": turns, "magnitude": 0 }));
+            }
+            object.insert("ai".into(), confused.get("previous_ai").cloned().unwrap_or("Basic".into()));
+        }
+        object.entry("statuses").or_insert(statuses.into());
+
+        if let Some(fighter) = object.get_mut("fighter").and_then(Value::as_object_mut) {
+            fighter.entry("on_hit").or_insert(Value::Null);
+        }
+        if object.get("item") == Some(&Value::from("Confuse")) {
+            let effect = serde_json::json!({ "Afflict": { "kind": "Confused", "turns": 10, "magnitude": 0 } });
+            object.insert("item".into(), effect);
+        }
+    });
+    Ok(save)
+}
+
+/// Version 8 gave everything one action per player turn, rather than running on speed and energy
+fn migrate_v8(mut save: Value) -> Result<Value, SaveError> {
+    let game = save
+        .get_mut(0)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the game state first".into()))?;
+    game.entry("ticks").or_insert(0.into());
+
+    for_each_object(&mut save, |object| {
+        if let Some(fighter) = object.get_mut("fighter").and_then(Value::as_object_mut) {
+            fighter.entry("speed").or_insert(10.into());
+            fighter.entry("energy").or_insert(0.into());
+        }
+    });
+    Ok(save)
+}
+
+/// Version 9 kept objects in plain lists, with the player first on the current floor and left out
+/// of stored ones. They became slots in an `Objects` store, the player's slot empty on stored floors
+fn migrate_v9(mut save: Value) -> Result<Value, SaveError> {
+    let into_slots = |objects: Vec<Value>| {
+        let slots: Vec<Value> = objects.into_iter().map(|object| serde_json::json!({ "generation": 0, "object": object })).collect();
+        serde_json::json!({ "slots": slots })
+    };
+
+    if let Some(levels) = save.pointer_mut("/0/dungeon/levels").and_then(Value::as_array_mut) {
+        for level in levels {
+            if let Some(Value::Array(objects)) = level.get_mut("objects").map(Value::take) {
+                level["objects"] = into_slots(std::iter::once(Value::Null).chain(objects).collect());
+            }
+        }
+    }
+
+    match save.get_mut(1).map(Value::take) {
+        Some(Value::Array(objects)) => save[1] = into_slots(objects),
+        _ => return Err(SaveError::Corrupt("Expected the list of objects second".into())),
+    }
+    Ok(save)
+}
+
+/// Version 10 kept whole objects, with the player's inventory in the game state. Objects became
+/// lists of components, with the inventory one of them and stored floors keeping only positions
+fn migrate_v10(mut save: Value) -> Result<Value, SaveError> {
+    let mut objects = ComponentLists::default();
+    for list in [
+        "names", "positions", "renderables", "blockers", "players", "fighters", "statuses", "ais", "senses",
+        "items", "counts", "equipment", "ranged", "inventories",
+    ] {
+        objects.components.insert(list.into(), Value::Array(vec![]));
+    }
+
+    // The current floor keeps its ids, so the player stays first
+    let slots = match save.pointer_mut("/1/slots").map(Value::take) {
+        Some(Value::Array(slots)) => slots,
+        _ => return Err(SaveError::Corrupt("Expected the objects second".into())),
+    };
+    for mut slot in slots {
+        let generation = slot["generation"].as_u64().unwrap_or(0);
+        match slot.get_mut("object").map(Value::take) {
+            Some(object) if object.is_object() => {
+                objects.add(generation, object, true);
+            }
+            _ => objects.entities.push(serde_json::json!({ "generation": generation, "in_use": false })),
+        }
+    }
+    objects.set("players", 0, 0, Value::Null);
+
+    let inventory = match save.get_mut(0).and_then(|game| game.get_mut("inventory")).map(Value::take) {
+        Some(Value::Array(inventory)) => inventory,
+        _ => vec![],
+    };
+    let inventory: Vec<Value> = inventory.into_iter().map(|item| objects.add(0, item, false)).collect();
+    objects.set("inventories", 0, 0, inventory.into());
+    if let Some(game) = save.get_mut(0).and_then(Value::as_object_mut) {
+        game.remove("inventory");
+    }
+
+    // Stored floors only keep where their objects are
+    if let Some(levels) = save.pointer_mut("/0/dungeon/levels").and_then(Value::as_array_mut) {
+        for level in levels {
+            let slots = match level.pointer_mut("/objects/slots").map(Value::take) {
+                Some(Value::Array(slots)) => slots,
+                _ => vec![],
+            };
+            let positions: Vec<Value> = slots
+                .into_iter()
+                .filter_map(|mut slot| slot.get_mut("object").map(Value::take).filter(Value::is_object))
+                .map(|object| {
+                    let position = serde_json::json!([object["x"], object["y"]]);
+                    serde_json::json!([objects.add(0, object, false), position])
+                })
+                .collect();
+            level["objects"] = positions.into();
+        }
+    }
+
+    let mut lists = objects.components;
+    lists.insert("entities".into(), objects.entities.into());
+    save[1] = lists.into();
+    Ok(save)
+}
+
+/// Version 11 only gave the player an inventory. Every creature has one now, empty for monsters
+/// that were already about
+fn migrate_v11(mut save: Value) -> Result<Value, SaveError> {
+    let fighters = match save.pointer("/1/fighters") {
+        Some(Value::Array(fighters)) => fighters.clone(),
+        _ => return Err(SaveError::Corrupt("Expected the fighters among the objects".into())),
+    };
+    let Some(inventories) = save.pointer_mut("/1/inventories").and_then(Value::as_array_mut) else {
+        return Err(SaveError::Corrupt("Expected the inventories among the objects".into()));
+    };
+    if inventories.len() < fighters.len() {
+        inventories.resize(fighters.len(), Value::Null);
+    }
+    for (fighter, inventory) in fighters.iter().zip(inventories.iter_mut()) {
+        if !fighter.is_null() && inventory.is_null() {
+            *inventory = serde_json::json!([fighter[0], []]);
+        }
+    }
+    Ok(save)
+}
+
+/// Version 12 had no combat log, and no damage dice. Of the weapons only the starting dagger is known
+/// for sure, the rest fight like bare hands until replaced
+fn migrate_v12(mut save: Value) -> Result<Value, SaveError> {
+    let game = save
+        .get_mut(0)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the game state first".into()))?;
+    game.entry("combat_log").or_insert(serde_json::to_value(Messages::new())?);
+
+    let names = save.pointer("/1/names").and_then(Value::as_array).cloned().unwrap_or_default();
+    if let Some(equipment) = save.pointer_mut("/1/equipment").and_then(Value::as_array_mut) {
+        for (entry, name) in equipment.iter_mut().zip(&names) {
+            if entry.is_array() && name.get(1).and_then(Value::as_str) == Some("Dagger") {
+                // What the starting dagger rolled when dice were added
+                entry[1]["damage"] = "1d4".into();
+            }
+        }
+    }
+    Ok(save)
+}
+
+/// Version 13 had only hand and head slots. The left hand held daggers and shields, so it became
+/// the off hand, and the right hand the main hand. Bows carried over stay one-handed
+fn migrate_v13(mut save: Value) -> Result<Value, SaveError> {
+    if let Some(equipment) = save.pointer_mut("/1/equipment").and_then(Value::as_array_mut) {
+        for entry in equipment.iter_mut().filter(|entry| entry.is_array()) {
+            let slot = match entry[1]["slot"].as_str() {
+                Some("LeftHand") => "OffHand",
+                Some("RightHand") => "MainHand",
+                _ => continue,
+            };
+            entry[1]["slot"] = slot.into();
+        }
+    }
+    Ok(save)
+}
+
+/// Version 14 had no enchanted items, so none of its objects have an enchantment
+fn migrate_v14(mut save: Value) -> Result<Value, SaveError> {
+    let objects = save
+        .get_mut(1)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the objects second".into()))?;
+    objects.entry("enchantments").or_insert(Value::Array(vec![]));
+    Ok(save)
+}
+
+/// Version 15 named every item for what it is. Its runs carry on that way, with no appearances to
+/// learn and nothing unidentified
+fn migrate_v15(mut save: Value) -> Result<Value, SaveError> {
+    let game = save
+        .get_mut(0)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the game state first".into()))?;
+    game.entry("identification").or_insert(serde_json::to_value(Identification::default())?);
+
+    let objects = save
+        .get_mut(1)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| SaveError::Corrupt("Expected the objects second".into()))?;
+    objects.entry("unidentified").or_insert(Value::Array(vec![]));
+    Ok(save)
+}
+
+/// Version 16 had no evasion, defense made attacks miss as well. Nothing gets any evasion, so
+/// creatures already about are hit more often than new ones, but no harder
+fn migrate_v16(mut save: Value) -> Result<Value, SaveError> {
+    for (list, field) in [("fighters", "base_evasion"), ("equipment", "evasion_bonus")] {
+        let Some(entries) = save.pointer_mut(&format!("/1/{}", list)).and_then(Value::as_array_mut) else {
+            return Err(SaveError::Corrupt(format!("Expected the {} among the objects", list)));
+        };
+        for entry in entries.iter_mut().filter(|entry| entry.is_array()) {
+            entry[1][field] = 0.into();
+        }
+    }
+    Ok(save)
+}
+
+/// Version 18 shot and threw for a fixed amount of damage. It is kept, as dice that always roll it
+fn migrate_v18(mut save: Value) -> Result<Value, SaveError> {
+    let Some(entries) = save.pointer_mut("/1/ranged").and_then(Value::as_array_mut) else {
+        return Err(SaveError::Corrupt("Expected the ranged among the objects".into()));
+    };
+    for entry in entries.iter_mut().filter(|entry| entry.is_array()) {
+        for kind in ["Ammo", "Thrown", "Attack"] {
+            if let Some(damage) = entry[1].pointer_mut(&format!("/{}/damage", kind))
+                && let Some(flat) = damage.as_i64()
+            {
+                *damage = flat.to_string().into();
+            }
+        }
+    }
+    Ok(save)
+}
+
+/// The components of every object, being put together from whole objects by `migrate_v10`
+#[derive(Default)]
+struct ComponentLists {
+    entities: Vec<Value>,
+    components: serde_json::Map<String, Value>,
+}
+
+impl ComponentLists {
+    /// Split a version 10 object into components, returning its new id
+    fn add(&mut self, generation: u64, object: Value, on_floor: bool) -> Value {
+        let index = self.entities.len();
+        self.entities.push(serde_json::json!({ "generation": generation, "in_use": true }));
+
+        self.set("names", index, generation, object["name"].clone());
+        if on_floor {
+            self.set("positions", index, generation, serde_json::json!([object["x"], object["y"]]));
+        }
+        let renderable = serde_json::json!({
+            "char": object["char"],
+            "colour": object["colour"],
+            "always_visible": object["always_visible"],
+        });
+        self.set("renderables", index, generation, renderable);
+        if object["blocks"] == true {
+            self.set("blockers", index, generation, Value::Null);
+        }
+        if let Some(mut fighter) = object.get("fighter").cloned().filter(Value::is_object) {
+            fighter["level"] = object["level"].clone();
+            self.set("fighters", index, generation, fighter);
+            self.set("statuses", index, generation, object["statuses"].clone());
+        }
+        for (field, list) in [("ai", "ais"), ("senses", "senses"), ("item", "items"), ("equipment", "equipment"), ("ranged", "ranged")] {
+            if let Some(component) = object.get(field).filter(|value| !value.is_null()) {
+                self.set(list, index, generation, component.clone());
+            }
+        }
+        if !object["item"].is_null() {
+            self.set("counts", index, generation, object["count"].clone());
+        }
+        serde_json::json!({ "index": index, "generation": generation })
+    }
+
+    /// Give object `index` a component, in the list with this name
+    fn set(&mut self, list: &str, index: usize, generation: u64, component: Value) {
+        if let Some(entries) = self.components.get_mut(list).and_then(Value::as_array_mut) {
+            if entries.len() <= index {
+                entries.resize(index + 1, Value::Null);
+            }
+            entries[index] = serde_json::json!([generation, component]);
+        }
+    }
+}
+
+/// Run `f` on every object in a save body: the ones on the map, on the stored floors and the inventory
+fn for_each_object(save: &mut Value, mut f: impl FnMut(&mut serde_json::Map<String, Value>)) {
+    if let Some(inventory) = save.get_mut(0).and_then(|game| game.get_mut("inventory")).and_then(Value::as_array_mut) {
+        inventory.iter_mut().filter_map(Value::as_object_mut).for_each(&mut f);
+    }
+    if let Some(levels) = save.pointer_mut("/0/dungeon/levels").and_then(Value::as_array_mut) {
+        levels
+            .iter_mut()
+            .filter_map(|level| level.get_mut("objects"))
+            .flat_map(objects_in)
+            .for_each(&mut f);
+    }
+    if let Some(objects) = save.get_mut(1) {
+        objects_in(objects).for_each(&mut f);
+    }
+}
+
+/// The objects in a floor's list, either a plain one from before version 10 or the slots of an `Objects`
+fn objects_in(objects: &mut Value) -> impl Iterator<Item = &mut serde_json::Map<String, Value>> {
+    let list = match objects.get("slots") {
+        Some(_) => &mut objects["slots"],
+        None => objects,
+    };
+    list.as_array_mut()
+        .into_iter()
+        .flatten()
+        .map(|entry| match entry.get("generation") {
+            Some(_) => &mut entry["object"],
+            None => entry,
+        })
+        .filter_map(Value::as_object_mut)
+}
+
+
+/// 64-bit FNV-1a hash of the save body, to catch truncated or edited files
+fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A new game to save, with the content it's made from loaded first
+    fn new_save() -> (Game, Objects) {
+        load_content().expect("Content should load");
+        new_game(7)
+    }
+
+    /// Somewhere to save to that no other test uses
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}-{}.{}", GAME_DIR_NAME, name, std::process::id(), SAVE_EXTENSION))
+    }
+
+    fn state(game: &Game, objects: &Objects) -> Value {
+        serde_json::to_value((game, objects)).unwrap()
+    }
+
+    #[test]
+    fn json_saves_load_back_the_same() {
+        let (game, objects) = new_save();
+        let path = temp_path("json");
+
+        write_save(&path, &game, &objects, SaveFormat::Json).unwrap();
+        let (loaded_game, loaded_objects) = read_save(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(state(&loaded_game, &loaded_objects), state(&game, &objects));
+    }
+
+    #[test]
+    fn binary_saves_load_back_the_same() {
+        let (game, objects) = new_save();
+        let path = temp_path("binary");
+
+        write_save(&path, &game, &objects, SaveFormat::Binary).unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(BINARY_MAGIC));
+        let (loaded_game, loaded_objects) = read_save(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(state(&loaded_game, &loaded_objects), state(&game, &objects));
+    }
+
+    #[test]
+    fn edited_saves_fail_the_checksum() {
+        let (game, objects) = new_save();
+        let path = temp_path("edited");
+        write_save(&path, &game, &objects, SaveFormat::Json).unwrap();
+
+        // Change a digit somewhere in the body, leaving it valid JSON
+        let mut contents = fs::read(&path).unwrap();
+        let body_start = contents.iter().position(|&byte| byte == b'\n').unwrap() + 1;
+        let digit = body_start + contents[body_start..].iter().position(u8::is_ascii_digit).unwrap();
+        contents[digit] = if contents[digit] == b'1' { b'2' } else { b'1' };
+        fs::write(&path, contents).unwrap();
+
+        let result = read_save(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(SaveError::Corrupt(reason)) if reason == "Checksum mismatch"));
+    }
+
+    #[test]
+    fn saves_from_newer_versions_are_refused() {
+        let body = b"[]";
+        let header = SaveHeader { magic: SAVE_MAGIC.into(), version: SAVE_VERSION + 1, checksum: checksum(body), metadata: None };
+        let path = temp_path("newer");
+        fs::write(&path, format!("{}\n[]", serde_json::to_string(&header).unwrap())).unwrap();
+
+        let result = read_save(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(SaveError::TooNew(version)) if version == SAVE_VERSION + 1));
+    }
+
+    #[test]
+    fn version_0_saves_migrate_to_the_current_version() {
+        load_content().expect("Content should load");
+
+        let (game, objects) = read_save(Path::new("tests/fixtures/v0.save")).unwrap();
+
+        assert_eq!(game.dungeon_level, 6);
+        assert_eq!(objects.fighters[PLAYER].base_max_hp, 120);
+        assert_eq!(objects.inventories[PLAYER].len(), 10);
+        assert!(objects.inventories[PLAYER].iter().all(|&item| objects.items.contains(item) && objects.counts.contains(item)));
+        assert_eq!(objects.names[objects.inventories[PLAYER][1]], "Sword");
+        assert_eq!(objects.equipment[objects.inventories[PLAYER][1]].slot, Slot::MainHand);
+    }
+    #[test]
+    fn version_18_ranged_damage_becomes_flat_dice() {
+        let save = serde_json::json!([{}, { "ranged": [
+            null,
+            [0, { "Ammo": { "kind": "arrow", "damage": 4 } }],
+            [0, { "Launcher": { "ammo": "arrow", "power": 2, "range": 8 } }],
+        ] }]);
+
+        let migrated = migrate_v18(save).unwrap();
+
+        let ammo: Ranged = serde_json::from_value(migrated[1]["ranged"][1][1].clone()).unwrap();
+        assert_eq!(ammo, Ranged::Ammo { kind: "arrow".into(), damage: Dice::flat(4) });
+        assert_eq!(migrated[1]["ranged"][2][1]["Launcher"]["power"], 2);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::*;
+
+/// How much energy something gains each tick. Haste doubles it and slowness halves it
+pub fn speed(id: ObjectId, objects: &Objects) -> i32 {
+    let speed = objects.fighters.get(id).map_or(0, |f| f.speed);
+    if objects.has_status(id, StatusKind::Hasted) {
+        speed * 2
+    } else if objects.has_status(id, StatusKind::Slowed) {
+        (speed / 2).max(1)
+    } else {
+        speed
+    }
+}
+
+/// Use up energy on an action. Nothing gets to act again until it is back up to 0
+pub fn spend_energy(id: ObjectId, cost: i32, objects: &mut Objects) {
+    if let Some(fighter) = objects.fighters.get_mut(id) {
+        fighter.energy -= cost;
+    }
+}
+
+fn is_ready(id: ObjectId, objects: &Objects) -> bool {
+    objects.fighters.get(id).is_some_and(|f| f.energy >= 0)
+}
+
+/// Run the clock until the player has the energy to act again. Each tick everything on the floor gains
+/// energy by its speed and monsters act whenever they have enough, so fast ones get more done
+pub fn pass_time(game: &mut Game, objects: &mut Objects) {
+    while objects.is_alive(PLAYER) && !is_ready(PLAYER, objects) {
+        game.ticks += 1;
+        let new_turn = game.ticks.is_multiple_of(TICKS_PER_TURN);
+
+        for id in objects.positions.ids() {
+            // Something that acted earlier this tick may have taken it away
+            if !objects.positions.contains(id) {
+                continue;
+            }
+            if new_turn {
+                tick_statuses(id, game, objects);
+            }
+
+            let speed = speed(id, objects);
+            if let Some(fighter) = objects.fighters.get_mut(id) {
+                fighter.energy += speed;
+            }
+            if objects.ais.contains(id) && is_ready(id, objects) {
+                ai_take_turn(id, game, objects);
+            }
+        }
+    }
+}
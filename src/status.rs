@@ -0,0 +1,194 @@
+use std::fmt;
+
+use crate::*;
+
+/// A timed effect on a creature, such as poison or haste
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32,
+    #[serde(default)]
+    pub magnitude: i32,     // Damage or healing each turn, for the kinds that have any
+    #[serde(default)]
+    pub source: Option<ObjectId>,   // Who put it there, and gets the XP if it kills
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum StatusKind {
+    /// Stumbles about at random
+    Confused,
+    /// Loses `magnitude` HP each turn. Stacks up, each dose adding to the damage
+    Poisoned,
+    /// Gains `magnitude` HP each turn
+    Regenerating,
+    /// Acts twice as often. Cancels out slowness
+    Hasted,
+    /// Acts half as often. Cancels out haste
+    Slowed,
+}
+
+impl StatusKind {
+    /// The kind that cancels this one out, and is cancelled by it
+    fn opposite(self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Hasted => Some(StatusKind::Slowed),
+            StatusKind::Slowed => Some(StatusKind::Hasted),
+            _ => None,
+        }
+    }
+
+    pub fn colour(self) -> SerializableColour {
+        match self {
+            StatusKind::Confused => LIGHT_GREEN,
+            StatusKind::Poisoned => GREEN,
+            StatusKind::Regenerating => LIGHT_VIOLET,
+            StatusKind::Hasted => LIGHT_CYAN,
+            StatusKind::Slowed => LIGHT_BLUE,
+        }
+    }
+}
+
+impl fmt::Display for StatusKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            StatusKind::Confused => "confused",
+            StatusKind::Poisoned => "poisoned",
+            StatusKind::Regenerating => "regenerating",
+            StatusKind::Hasted => "hasted",
+            StatusKind::Slowed => "slowed",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Objects {
+    pub fn has_status(&self, id: ObjectId, kind: StatusKind) -> bool {
+        self.statuses.get(id).is_some_and(|statuses| statuses.iter().any(|status| status.kind == kind))
+    }
+}
+
+/// Whether the player would notice an effect on `id`: one on themselves, or on something in view
+fn noticed(id: ObjectId, game: &Game, objects: &Objects) -> bool {
+    id == PLAYER || objects.positions.get(id).is_some_and(|&(x, y)| game.fov.is_in_fov(x, y))
+}
+
+/// Put an effect on an object, on behalf of `source`. An effect of a kind it already has lasts as long
+/// as the longer of the two, poison gets stronger and whoever added to it last takes the credit.
+/// Haste and slowness cancel each other out instead
+pub fn add_status(id: ObjectId, effect: StatusEffect, source: ObjectId, game: &mut Game, objects: &mut Objects) {
+    let effect = StatusEffect { source: Some(source), ..effect };
+    let noticed = noticed(id, game, objects);
+    let Some(statuses) = objects.statuses.get_mut(id) else {
+        return;     // Only creatures can be affected
+    };
+    let name = &objects.names[id];
+
+    if let Some(opposite) = effect.kind.opposite()
+        && let Some(index) = statuses.iter().position(|status| status.kind == opposite)
+    {
+        statuses.remove(index);
+        if noticed {
+            game.messages.add(format!("{} is no longer {}.", name, opposite), WHITE);
+        }
+        return;
+    }
+
+    match statuses.iter_mut().find(|status| status.kind == effect.kind) {
+        Some(status) => {
+            status.turns = status.turns.max(effect.turns);
+            status.magnitude = match effect.kind {
+                StatusKind::Poisoned => status.magnitude + effect.magnitude,
+                _ => status.magnitude.max(effect.magnitude),
+            };
+            status.source = effect.source;
+        }
+        None => statuses.push(effect),
+    }
+    if noticed {
+        game.messages.add(format!("{} is {}!", name, effect.kind), effect.kind.colour());
+    }
+}
+
+/// Apply every effect on an object for one turn, and remove the ones that have run out
+pub fn tick_statuses(id: ObjectId, game: &mut Game, objects: &mut Objects) {
+    let Some(mut statuses) = objects.statuses.get_mut(id).map(std::mem::take) else {
+        return;
+    };
+    for status in &statuses {
+        match status.kind {
+            StatusKind::Poisoned => {
+                // Poisoning yourself is worth nothing
+                if let Some(xp) = take_damage(id, status.magnitude, game, objects)
+                    && let Some(source) = status.source.filter(|&source| source != id)
+                    && let Some(fighter) = objects.fighters.get_mut(source)
+                {
+                    fighter.xp += xp;
+                }
+            }
+            StatusKind::Regenerating => heal(id, status.magnitude, objects),
+            _ => {}
+        }
+    }
+
+    for status in statuses.iter_mut() {
+        status.turns -= 1;
+        if status.turns <= 0 && objects.is_alive(id) && noticed(id, game, objects) {
+            game.messages.add(format!("{} is no longer {}.", objects.names[id], status.kind), WHITE);
+        }
+    }
+    statuses.retain(|status| status.turns > 0);
+
+    // The dead don't keep anything
+    if objects.is_alive(id) {
+        objects.statuses[id] = statuses;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POISON: StatusEffect = StatusEffect { kind: StatusKind::Poisoned, turns: 3, magnitude: 1, source: None };
+
+    /// A new game and one of the monsters on its first floor. Nothing is in view until the game
+    /// loop first works out what the player can see
+    fn with_unseen_monster() -> (Game, Objects, ObjectId) {
+        load_content().expect("Content should load");
+        let (game, objects) = new_game(11);
+        let monster = objects.fighters.ids().into_iter().find(|&id| id != PLAYER).expect("The first floor should have monsters");
+        (game, objects, monster)
+    }
+
+    #[test]
+    fn effects_out_of_view_pass_without_a_message() {
+        let (mut game, mut objects, monster) = with_unseen_monster();
+        let messages = game.messages.iter().count();
+
+        add_status(monster, POISON, PLAYER, &mut game, &mut objects);
+        for _ in 0..POISON.turns {
+            tick_statuses(monster, &mut game, &mut objects);
+        }
+        assert_eq!(game.messages.iter().count(), messages);
+
+        add_status(PLAYER, POISON, PLAYER, &mut game, &mut objects);
+        assert_eq!(game.messages.iter().count(), messages + 1);
+    }
+    #[test]
+    fn poison_kills_are_worth_xp_only_to_whoever_poisoned() {
+        let lethal = StatusEffect { magnitude: 1000, ..POISON };
+
+        let (mut game, mut objects, monster) = with_unseen_monster();
+        add_status(monster, lethal, monster, &mut game, &mut objects);
+        tick_statuses(monster, &mut game, &mut objects);
+        assert!(!objects.is_alive(monster));
+        assert_eq!(objects.fighters[PLAYER].xp, 0);
+
+        let (mut game, mut objects, monster) = with_unseen_monster();
+        add_status(monster, lethal, PLAYER, &mut game, &mut objects);
+        tick_statuses(monster, &mut game, &mut objects);
+        assert!(!objects.is_alive(monster));
+        assert!(objects.fighters[PLAYER].xp > 0);
+    }
+}
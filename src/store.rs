@@ -0,0 +1,217 @@
+use std::ops::{Index, IndexMut};
+
+use crate::*;
+
+/// Refers to one object for as long as it exists. Unlike an index into a list it stays put when
+/// other objects are removed, and never comes to mean another object once its own is gone
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ObjectId {
+    index: u32,
+    generation: u32,
+}
+
+/// One kind of component, for every object that has it. Kept by the object's index, together with
+/// the generation of the object it belongs to, so a stale id never finds another object's component
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Components<T> {
+    entries: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Components { entries: vec![] }
+    }
+}
+
+impl<T> Components<T> {
+    pub fn get(&self, id: ObjectId) -> Option<&T> {
+        match self.entries.get(id.index as usize) {
+            Some(Some((generation, component))) if *generation == id.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut T> {
+        match self.entries.get_mut(id.index as usize) {
+            Some(Some((generation, component))) if *generation == id.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Give an object this component, replacing any it already had
+    pub fn insert(&mut self, id: ObjectId, component: T) {
+        let index = id.index as usize;
+        if self.entries.len() <= index {
+            self.entries.resize_with(index + 1, || None);
+        }
+        self.entries[index] = Some((id.generation, component));
+    }
+
+    pub fn remove(&mut self, id: ObjectId) -> Option<T> {
+        let entry = self.entries.get_mut(id.index as usize)?;
+        match entry {
+            Some((generation, _)) if *generation == id.generation => entry.take().map(|(_, component)| component),
+            _ => None,
+        }
+    }
+
+    /// Every object with this component, in the order they act
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectId, &T)> {
+        self.entries.iter().enumerate().filter_map(|(index, entry)| {
+            let (generation, component) = entry.as_ref()?;
+            Some((ObjectId { index: index as u32, generation: *generation }, component))
+        })
+    }
+
+    /// The ids from `iter`, for when the objects get changed along the way
+    pub fn ids(&self) -> Vec<ObjectId> {
+        self.iter().map(|(id, _)| id).collect()
+    }
+}
+
+impl<T> Index<ObjectId> for Components<T> {
+    type Output = T;
+
+    fn index(&self, id: ObjectId) -> &T {
+        self.get(id).unwrap_or_else(|| panic!("Object {:?} has no {}", id, std::any::type_name::<T>()))
+    }
+}
+
+impl<T> IndexMut<ObjectId> for Components<T> {
+    fn index_mut(&mut self, id: ObjectId) -> &mut T {
+        self.get_mut(id).unwrap_or_else(|| panic!("Object {:?} has no {}", id, std::any::type_name::<T>()))
+    }
+}
+
+
+/// Every object in the game, as the components it is made of. What an object is and does comes
+/// from which components it has: a monster is one with a fighter and an AI, an item one with an item.
+/// Only objects on the current floor have a position; carried things and other floors have none
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Objects {
+    entities: Vec<Entity>,
+    pub names: Components<String>,
+    pub positions: Components<(i32, i32)>,
+    pub renderables: Components<Renderable>,
+    pub blockers: Components<Blocks>,
+    pub players: Components<Player>,
+    pub fighters: Components<Fighter>,
+    pub statuses: Components<Vec<StatusEffect>>,
+    pub ais: Components<Ai>,
+    pub senses: Components<Senses>,
+    pub items: Components<Item>,
+    pub counts: Components<u32>,     // How many there are, for items that stack
+    pub unidentified: Components<String>,   // What an item looks like while the player doesn't know what it is
+    pub equipment: Components<Equipment>,
+    pub enchantments: Components<Enchantment>,
+    pub ranged: Components<Ranged>,
+    pub inventories: Components<Vec<ObjectId>>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Entity {
+    generation: u32,    // Goes up every time the object is removed, so ids of it go stale
+    in_use: bool,
+}
+
+impl Objects {
+    /// A new object with nothing but a name, reusing the slot of a removed one if there is one
+    pub fn spawn(&mut self, name: &str) -> ObjectId {
+        let id = match self.entities.iter().position(|entity| !entity.in_use) {
+            Some(index) => {
+                let entity = &mut self.entities[index];
+                entity.in_use = true;
+                ObjectId { index: index as u32, generation: entity.generation }
+            }
+            None => {
+                self.entities.push(Entity { generation: 0, in_use: true });
+                ObjectId { index: self.entities.len() as u32 - 1, generation: 0 }
+            }
+        };
+        self.names.insert(id, name.into());
+        id
+    }
+
+    /// Remove an object and all its components. Its id, and any copies of it, no longer refer to anything
+    pub fn despawn(&mut self, id: ObjectId) {
+        assert!(id != PLAYER, "The player can't be removed");
+        assert!(self.contains(id), "Object {:?} was already removed", id);
+        let entity = &mut self.entities[id.index as usize];
+        entity.generation += 1;
+        entity.in_use = false;
+
+        self.names.remove(id);
+        self.positions.remove(id);
+        self.renderables.remove(id);
+        self.blockers.remove(id);
+        self.players.remove(id);
+        self.fighters.remove(id);
+        self.statuses.remove(id);
+        self.ais.remove(id);
+        self.senses.remove(id);
+        self.items.remove(id);
+        self.counts.remove(id);
+        self.unidentified.remove(id);
+        self.equipment.remove(id);
+        self.enchantments.remove(id);
+        self.ranged.remove(id);
+        self.inventories.remove(id);
+    }
+
+    /// A new object with a copy of every component of `id`, e.g. one item split off a stack
+    pub fn duplicate(&mut self, id: ObjectId) -> ObjectId {
+        let copy = self.spawn(&self.names[id].clone());
+        copy_component(&mut self.positions, id, copy);
+        copy_component(&mut self.renderables, id, copy);
+        copy_component(&mut self.blockers, id, copy);
+        copy_component(&mut self.players, id, copy);
+        copy_component(&mut self.fighters, id, copy);
+        copy_component(&mut self.statuses, id, copy);
+        copy_component(&mut self.ais, id, copy);
+        copy_component(&mut self.senses, id, copy);
+        copy_component(&mut self.items, id, copy);
+        copy_component(&mut self.counts, id, copy);
+        copy_component(&mut self.unidentified, id, copy);
+        copy_component(&mut self.equipment, id, copy);
+        copy_component(&mut self.enchantments, id, copy);
+        copy_component(&mut self.ranged, id, copy);
+        copy_component(&mut self.inventories, id, copy);
+        copy
+    }
+
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.entities
+            .get(id.index as usize)
+            .is_some_and(|entity| entity.in_use && entity.generation == id.generation)
+    }
+
+    /// Take everything but the player off the current floor, and where it was to put it back later
+    pub fn take_floor(&mut self) -> Vec<(ObjectId, (i32, i32))> {
+        let floor = self.positions.iter().filter(|&(id, _)| id != PLAYER).map(|(id, &pos)| (id, pos)).collect::<Vec<_>>();
+        for &(id, _) in &floor {
+            self.positions.remove(id);
+        }
+        floor
+    }
+
+    /// Bring back a floor taken by `take_floor`
+    pub fn restore_floor(&mut self, floor: Vec<(ObjectId, (i32, i32))>) {
+        for (id, pos) in floor {
+            self.positions.insert(id, pos);
+        }
+    }
+}
+
+fn copy_component<T: Clone>(components: &mut Components<T>, from: ObjectId, to: ObjectId) {
+    if let Some(component) = components.get(from).cloned() {
+        components.insert(to, component);
+    }
+}
+
+/// The player is always the first object spawned
+pub const PLAYER: ObjectId = ObjectId { index: 0, generation: 0 };
@@ -1,93 +1,76 @@
-use tcod::input::{self, Event};
-
-use crate::*;
-
-#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
-pub struct Tile {
-    pub blocked: bool,
-    pub block_sight: bool,
-    pub explored: bool,
-}
-
-impl Tile {
-    pub fn empty() -> Self {
-        Tile {
-            blocked: false,
-            block_sight: false,
-            explored: false,
-        }
-    }
-
-    pub fn wall() -> Self {
-        Tile {
-            blocked: true,
-            block_sight: true,
-            explored: false,
-        }
-    }
-
-    pub fn is_walkable(&self) -> bool {
-        !self.blocked
-    }
-}
-
-pub fn target_tile(tcod: &mut Tcod, game: &mut Game, objects: &[Object], max_range: Option<f32>) -> Option<(i32, i32)> {
-    use tcod::input::KeyCode::Escape;
-
-    loop {
-        // Render the screen
-        // This erases the inventory and shows the name of objects under the mouse
-        tcod.root.flush();
-        let event = input::check_for_event(input::KEY_PRESS | input::MOUSE).map(|e| e.1);
-
-        match event {
-            Some(Event::Mouse(m)) => tcod.mouse = m,
-            Some(Event::Key(k)) => tcod.key = k,
-            None => tcod.key = Default::default(),
-        }
-        render_all(tcod, game, objects, false);
-
-        let (x, y) = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
-
-        // Accept the target if the player clicked in FOV and in range
-        // If one is specified
-        let in_fov = (x < MAP_WIDTH) && (y < MAP_HEIGHT) && tcod.fov.is_in_fov(x, y);
-        let in_range = max_range.map_or(true, |range| objects[PLAYER].distance(x, y) <= range);
-
-        if tcod.mouse.lbutton_pressed && in_fov && in_range { return Some((x, y)); }
-        if tcod.mouse.rbutton_pressed || tcod.key.code == Escape { return None; }   // Cancelled
-    }
-}
-
-
-#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
-pub struct Rect {
-    pub x1: i32,
-    pub y1: i32,
-    pub x2: i32,
-    pub y2: i32,
-}
-
-impl Rect {
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
-        Rect {
-            x1: x,
-            y1: y,
-            x2: x + w,
-            y2: y + h,
-        }
-    }
-
-    pub fn center(&self) -> (i32, i32) {
-        let center_x = (self.x1 + self.x2) / 2;
-        let center_y = (self.y1 + self.y2) / 2;
-        (center_x, center_y)
-    }
-
-    pub fn intersects_with(&self, other: &Rect) -> bool {
-        (self.x1 <= other.x2)
-            && (self.x2 >= other.x1)
-            && (self.y1 <= other.y2)
-            && (self.y2 >= other.y1)
-    }
+use crate::*;
+
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Tile {
+    pub blocked: bool,
+    pub block_sight: bool,
+    pub explored: bool,
+}
+
+impl Tile {
+    pub fn empty() -> Self {
+        Tile {
+            blocked: false,
+            block_sight: false,
+            explored: false,
+        }
+    }
+
+    pub fn wall() -> Self {
+        Tile {
+            blocked: true,
+            block_sight: true,
+            explored: false,
+        }
+    }
+
+    pub fn is_walkable(&self) -> bool {
+        !self.blocked
+    }
+}
+
+/// Ask the frontend for a target tile. Choices out of FOV or range are treated as cancelled
+pub fn target_tile(frontend: &mut dyn Frontend, game: &Game, objects: &Objects, max_range: Option<f32>) -> Option<(i32, i32)> {
+    let (x, y) = frontend.choose_target_tile(game, objects, max_range)?;
+    if is_valid_target(x, y, game, objects, max_range) { Some((x, y)) } else { None }
+}
+
+/// Whether the player may target (x, y): it must be in FOV and in range, if one is specified
+pub fn is_valid_target(x: i32, y: i32, game: &Game, objects: &Objects, max_range: Option<f32>) -> bool {
+    let in_fov = (x < MAP_WIDTH) && (y < MAP_HEIGHT) && game.fov.is_in_fov(x, y);
+    let in_range = max_range.is_none_or(|range| objects.distance(PLAYER, x, y) <= range);
+    in_fov && in_range
+}
+
+
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect {
+            x1: x,
+            y1: y,
+            x2: x + w,
+            y2: y + h,
+        }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        let center_x = (self.x1 + self.x2) / 2;
+        let center_y = (self.y1 + self.y2) / 2;
+        (center_x, center_y)
+    }
+
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        (self.x1 <= other.x2)
+            && (self.x2 >= other.x1)
+            && (self.y1 <= other.y2)
+            && (self.y2 >= other.y1)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+use roguelike::*;
+
+const SEED: u64 = 42;
+
+/// A new game, with the content it's made from loaded first
+fn start() -> (Game, Objects) {
+    load_content().expect("Content should load");
+    new_game(SEED)
+}
+
+/// Play `inputs` through from a new game, returning the frontend to check how the script went
+fn play(inputs: Vec<Input>, game: &mut Game, objects: &mut Objects) -> ScriptedFrontend {
+    let mut frontend = ScriptedFrontend::new(inputs);
+    play_game(&mut frontend, game, objects);
+    frontend
+}
+
+fn last_message(game: &Game) -> &str {
+    game.messages.iter().last().map(|(message, _)| message.as_str()).unwrap_or_default()
+}
+
+#[test]
+fn waiting_passes_turns_in_place() {
+    let (mut game, mut objects) = start();
+    let start_position = objects.pos(PLAYER);
+
+    let frontend = play(vec![Input::Command(Command::Wait); 3], &mut game, &mut objects);
+
+    assert_eq!(frontend.error(), None);
+    assert_eq!(game.turns, 3);
+    assert_eq!(objects.pos(PLAYER), start_position);
+    assert_eq!(objects.fighters[PLAYER].hp, 100);
+    assert!(last_message(&game).starts_with("Welcome stranger!"));
+}
+
+#[test]
+fn walking_moves_the_player() {
+    let (mut game, mut objects) = start();
+    let (x, y) = objects.pos(PLAYER);
+    let (dx, dy) = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (-1, -1), (1, -1), (-1, 1)]
+        .into_iter()
+        .find(|&(dx, dy)| !game.map[(x + dx) as usize][(y + dy) as usize].blocked && objects.blocker_at(x + dx, y + dy).is_none())
+        .expect("The player should start next to open floor");
+
+    let frontend = play(vec![Input::Command(Command::Move { dx, dy })], &mut game, &mut objects);
+
+    assert_eq!(frontend.error(), None);
+    assert_eq!(game.turns, 1);
+    assert_eq!(objects.pos(PLAYER), (x + dx, y + dy));
+}
+
+#[test]
+fn the_same_script_plays_out_the_same() {
+    let inputs = vec![Input::Command(Command::Wait); 20];
+    let (mut first, mut first_objects) = start();
+    let (mut second, mut second_objects) = start();
+
+    play(inputs.clone(), &mut first, &mut first_objects);
+    play(inputs, &mut second, &mut second_objects);
+
+    assert_eq!(first_objects.pos(PLAYER), second_objects.pos(PLAYER));
+    assert_eq!(first_objects.fighters[PLAYER].hp, second_objects.fighters[PLAYER].hp);
+    assert_eq!(first.messages.iter().count(), second.messages.iter().count());
+}
+
+#[test]
+fn dropping_the_dagger_leaves_it_underfoot() {
+    let (mut game, mut objects) = start();
+    let dagger = objects.inventories[PLAYER][0];
+
+    let frontend = play(vec![Input::Command(Command::DropItem), Input::Inventory(Some(0))], &mut game, &mut objects);
+
+    assert_eq!(frontend.error(), None);
+    assert!(objects.inventories[PLAYER].is_empty());
+    assert_eq!(objects.pos(dagger), objects.pos(PLAYER));
+    assert_eq!(last_message(&game), "You dropped your Dagger.");
+    assert_eq!(game.turns, 0);
+}
+
+#[test]
+fn choosing_a_missing_item_fails_the_script() {
+    let (mut game, mut objects) = start();
+
+    let inputs = vec![Input::Command(Command::UseItem), Input::Inventory(Some(5)), Input::Command(Command::Wait)];
+    let frontend = play(inputs, &mut game, &mut objects);
+
+    assert!(frontend.error().is_some_and(|error| error.contains("inventory item 5")));
+    assert_eq!(objects.inventories[PLAYER].len(), 1);
+    assert_eq!(game.turns, 0);
+}
+
+#[test]
+fn the_wrong_kind_of_input_fails_the_script() {
+    let (mut game, mut objects) = start();
+
+    let inputs = vec![Input::Target(None), Input::Command(Command::Wait)];
+    let frontend = play(inputs, &mut game, &mut objects);
+
+    assert!(frontend.error().is_some_and(|error| error.contains("expected a command")));
+    assert_eq!(game.turns, 0);
+}
+
+#[test]
+fn targeting_outside_the_map_fails_the_script() {
+    let (mut game, mut objects) = start();
+
+    let inputs = vec![Input::Command(Command::Throw), Input::Inventory(Some(0)), Input::Target(Some((-3, 500)))];
+    let frontend = play(inputs, &mut game, &mut objects);
+
+    assert!(frontend.error().is_some_and(|error| error.contains("can't be targeted")));
+    assert_eq!(objects.inventories[PLAYER].len(), 1);
+}